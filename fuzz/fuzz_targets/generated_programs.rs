@@ -0,0 +1,22 @@
+//! Feeds generated programs through every stage of the compiler, which has to accept all of them.
+//!
+//! Run with `cargo fuzz run generated_programs` from the root of the repository.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pimpf::generator::{self, Bytes};
+
+fuzz_target!(|data: &[u8]| {
+    let program = generator::generate(&mut Bytes(data));
+    let code = program.to_string();
+
+    let parsed = pimpf::parse(&code).expect("generated programs parse");
+    assert_eq!(
+        parsed.clone().without_parentheses(),
+        program.without_parentheses(),
+        "printing and parsing changed the program:\n{}",
+        code
+    );
+    pimpf::analyze(&parsed).expect("generated programs are well-typed");
+    pimpf::compile_to_asm(&code, true).expect("generated programs compile");
+});
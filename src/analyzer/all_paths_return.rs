@@ -0,0 +1,47 @@
+use crate::grammar::{visit::Visitor, *};
+
+pub fn analyze<'src>(p: &Program<'src>) -> Result<(), ()> {
+    AllPathsReturn.visit_program(p)
+}
+
+struct AllPathsReturn;
+
+impl<'src> Visitor<'src> for AllPathsReturn {
+    type Error = ();
+
+    fn visit_function(&mut self, function: &Function<'src>) -> Result<(), ()> {
+        // `void` functions may simply run off their end
+        if function.return_type == Type::Void {
+            return Ok(());
+        }
+
+        // without nested blocks there's only a single path through the body
+        match function
+            .statements
+            .iter()
+            .any(|s| matches!(s, Statement::Return(_)))
+        {
+            true => Ok(()),
+            false => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(code: &str) -> Result<(), ()> {
+        analyze(&crate::parse(code).unwrap())
+    }
+
+    #[test]
+    fn missing_return() {
+        assert!(check("int f() { int x = 1; } int main() { return 0; }").is_err());
+    }
+
+    #[test]
+    fn void_without_return() {
+        assert!(check("void f() { int x = 1; } int main() { return 0; }").is_ok());
+    }
+}
@@ -1,13 +1,30 @@
-mod no_return_in_main;
+mod all_paths_return;
 mod num_range;
+mod type_check;
+mod unsupported_features;
 mod variable_dec_init_and_usage;
 
 use crate::grammar::Program;
 
-pub fn analyze<'src>(program: &Program<'src>) -> Result<(), ()> {
-    num_range::analyze(program)?;
-    variable_dec_init_and_usage::analyze(program)?;
-    no_return_in_main::analyze(program)?;
+pub type Check = fn(&Program<'_>) -> Result<(), ()>;
+
+/// The checks in the order they run, later checks rely on the earlier ones.
+pub const CHECKS: &[(&str, Check)] = &[
+    ("unsupported_features", unsupported_features::analyze),
+    ("num_range", num_range::analyze),
+    (
+        "variable_dec_init_and_usage",
+        variable_dec_init_and_usage::analyze,
+    ),
+    ("type_check", type_check::analyze),
+    ("all_paths_return", all_paths_return::analyze),
+];
+
+/// Runs all checks, returns the name of the first one which fails.
+pub fn analyze<'src>(program: &Program<'src>) -> Result<(), &'static str> {
+    for (name, check) in CHECKS {
+        check(program).map_err(|_| *name)?;
+    }
 
     Ok(())
 }
@@ -1,75 +1,20 @@
-use crate::grammar::*;
+use crate::grammar::{visit::Visitor, *};
 
 pub fn analyze<'src>(p: &Program<'src>) -> Result<(), ()> {
-    analyze_statements(&p.0)
+    NumRange.visit_program(p)
 }
 
-fn analyze_statements<'src>(statements: &Statements<'src>) -> Result<(), ()> {
-    match statements {
-        Statements::None => Ok(()),
-        Statements::Statement { current, next } => {
-            analyze_statement(current)?;
-            analyze_statements(next)
-        }
-    }
-}
-
-fn analyze_statement<'src>(statement: &Statement<'src>) -> Result<(), ()> {
-    match statement {
-        Statement::Decl(decl) => analyze_decl(decl),
-        Statement::Simp(simp) => analyze_simp(simp),
-        Statement::Return(exp) => analyze_exp(exp),
-    }
-}
-
-fn analyze_decl<'src>(decl: &Declaration<'src>) -> Result<(), ()> {
-    match decl {
-        Declaration::Ident(ident) => analyze_ident(ident),
-        Declaration::IdentExp { ident, exp } => {
-            analyze_ident(ident)?;
-            analyze_exp(exp)
-        }
-    }
-}
-
-fn analyze_simp<'src>(simp: &SimpleInstruction<'src>) -> Result<(), ()> {
-    let SimpleInstruction { lvalue, exp, .. } = simp;
+/// Checks that every integer constant fits into 32 bits.
+struct NumRange;
 
-    analyze_lvalue(lvalue)?;
-    analyze_exp(exp)?;
+impl<'src> Visitor<'src> for NumRange {
+    type Error = ();
 
-    Ok(())
-}
-
-fn analyze_exp<'src>(exp: &Expression<'src>) -> Result<(), ()> {
-    match exp {
-        Expression::NestedExp(nested_exp) => analyze_exp(nested_exp),
-        Expression::Intconst(intconst) => analyze_intconst(intconst),
-        Expression::Ident(ident) => analyze_ident(ident),
-        Expression::Binop { left, right, .. } => {
-            analyze_exp(left)?;
-            analyze_exp(right)?;
-            Ok(())
+    fn visit_intconst(&mut self, intconst: &Intconst<'src>) -> Result<(), ()> {
+        match intconst {
+            Intconst::Decnum(decnum) => analyze_decnum(decnum),
+            Intconst::Hexnum(hexnum) => analyze_hexnum(hexnum),
         }
-        Expression::Unop { right, .. } => analyze_exp(right),
-    }
-}
-
-fn analyze_ident<'src>(_ident: &Identifier<'src>) -> Result<(), ()> {
-    Ok(())
-}
-
-fn analyze_lvalue<'src>(lvalue: &LValue<'src>) -> Result<(), ()> {
-    match lvalue {
-        LValue::Ident(ident) => analyze_ident(ident),
-        LValue::LValue(lvalue) => analyze_lvalue(lvalue),
-    }
-}
-
-fn analyze_intconst<'src>(intconst: &Intconst<'src>) -> Result<(), ()> {
-    match intconst {
-        Intconst::Decnum(decnum) => analyze_decnum(decnum),
-        Intconst::Hexnum(hexnum) => analyze_hexnum(hexnum),
     }
 }
 
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+
+use crate::{grammar::*, library::Builtin};
+
+pub fn analyze<'src>(p: &Program<'src>) -> Result<(), ()> {
+    let signatures = signatures(p)?;
+
+    for function in &p.0 {
+        let mut ana = Analyzer::new(&signatures, function.return_type);
+        ana.analyze(function)?;
+    }
+
+    Ok(())
+}
+
+/// The types of the parameters and the return type of a function.
+type Signature = (Vec<Type>, Type);
+
+/// Collects the signatures of all functions and checks that there's a proper `main` function.
+fn signatures<'src>(p: &Program<'src>) -> Result<HashMap<&'src str, Signature>, ()> {
+    let mut signatures = HashMap::new();
+
+    for function in &p.0 {
+        let params: Vec<Type> = function.params.iter().map(|param| param.ty).collect();
+        if params.contains(&Type::Void) {
+            return Err(());
+        }
+
+        let is_builtin = Builtin::from_name(function.ident.0).is_some();
+        let signature = (params, function.return_type);
+        if is_builtin || signatures.insert(function.ident.0, signature).is_some() {
+            return Err(());
+        }
+    }
+
+    match signatures.get("main") {
+        Some((params, Type::Int)) if params.is_empty() => Ok(signatures),
+        _ => Err(()),
+    }
+}
+
+#[derive(Debug)]
+struct Analyzer<'a, 'src> {
+    signatures: &'a HashMap<&'src str, Signature>,
+    types: HashMap<&'src str, Type>,
+    return_type: Type,
+    /// The type of `\result`, only set while checking an `ensures` annotation.
+    result: Option<Type>,
+}
+
+impl<'a, 'src> Analyzer<'a, 'src> {
+    fn new(signatures: &'a HashMap<&'src str, Signature>, return_type: Type) -> Self {
+        Self {
+            signatures,
+            types: HashMap::new(),
+            return_type,
+            result: None,
+        }
+    }
+
+    fn analyze(&mut self, function: &Function<'src>) -> Result<(), ()> {
+        for param in &function.params {
+            self.types.insert(param.ident.0, param.ty);
+        }
+
+        for annotation in &function.annotations {
+            match annotation {
+                Annotation::Requires(exp) => self.expect_type(exp, Type::Bool)?,
+                Annotation::Ensures(exp) => {
+                    if self.return_type != Type::Void {
+                        self.result = Some(self.return_type);
+                    }
+                    self.expect_type(exp, Type::Bool)?;
+                    self.result = None;
+                }
+                Annotation::Assert(_) => return Err(()),
+                Annotation::LoopInvariant(_) => {
+                    unreachable!("checked by the unsupported_features analyzer")
+                }
+            }
+        }
+
+        self.analyze_statements(&function.statements)
+    }
+
+    fn analyze_statements(&mut self, statements: &[Statement<'src>]) -> Result<(), ()> {
+        for statement in statements {
+            self.analyze_statement(statement)?;
+        }
+
+        Ok(())
+    }
+
+    fn analyze_statement(&mut self, statement: &Statement<'src>) -> Result<(), ()> {
+        match statement {
+            Statement::Decl(decl) => self.analyze_decl(decl),
+            Statement::Simp(simp) => self.analyze_simp(simp),
+            Statement::Return(None) if self.return_type == Type::Void => Ok(()),
+            Statement::Return(Some(exp)) if self.return_type != Type::Void => {
+                self.expect_type(exp, self.return_type)
+            }
+            Statement::Return(_) => Err(()),
+            Statement::Assert(exp) => self.expect_type(exp, Type::Bool),
+            Statement::Print(exp) => match self.type_of_exp(exp)? {
+                Type::Int | Type::Bool | Type::String => Ok(()),
+                _ => Err(()),
+            },
+            Statement::Exp(exp) => self.type_of_exp(exp).map(|_| ()),
+            Statement::Annotations(annotations) => {
+                for annotation in annotations {
+                    match annotation {
+                        Annotation::Assert(exp) => self.expect_type(exp, Type::Bool)?,
+                        Annotation::LoopInvariant(_) => {
+                            unreachable!("checked by the unsupported_features analyzer")
+                        }
+                        Annotation::Requires(_) | Annotation::Ensures(_) => return Err(()),
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    fn analyze_decl(&mut self, decl: &Declaration<'src>) -> Result<(), ()> {
+        match decl {
+            Declaration::Ident { ty: Type::Void, .. }
+            | Declaration::IdentExp { ty: Type::Void, .. } => Err(()),
+            Declaration::Ident { ty, ident } => {
+                self.types.insert(ident.0, *ty);
+                Ok(())
+            }
+            Declaration::IdentExp { ty, ident, exp } => {
+                self.expect_type(exp, *ty)?;
+                self.types.insert(ident.0, *ty);
+                Ok(())
+            }
+        }
+    }
+
+    fn analyze_simp(&mut self, simp: &SimpleInstruction<'src>) -> Result<(), ()> {
+        let SimpleInstruction { lvalue, asnop, exp } = simp;
+
+        let lvalue_type = self.type_of_lvalue(lvalue)?;
+        match asnop {
+            AsNop::Equal => self.expect_type(exp, lvalue_type),
+            _ if lvalue_type == Type::Int => self.expect_type(exp, Type::Int),
+            _ => Err(()),
+        }
+    }
+
+    /// Returns the type of an expression which has to produce a value.
+    fn expect_value(&self, exp: &Expression<'src>) -> Result<Type, ()> {
+        match self.type_of_exp(exp)? {
+            Type::Void => Err(()),
+            ty => Ok(ty),
+        }
+    }
+
+    fn expect_type(&self, exp: &Expression<'src>, expected: Type) -> Result<(), ()> {
+        if self.type_of_exp(exp)? != expected {
+            return Err(());
+        }
+
+        Ok(())
+    }
+
+    /// Returns the type of the expression where only calls may be of type `void`.
+    fn type_of_exp(&self, exp: &Expression<'src>) -> Result<Type, ()> {
+        match exp {
+            Expression::NestedExp(nested_exp) => self.expect_value(nested_exp),
+            Expression::Intconst(_) => Ok(Type::Int),
+            Expression::Boolconst(_) => Ok(Type::Bool),
+            Expression::Charconst(_) => Ok(Type::Char),
+            Expression::Stringconst(_) => Ok(Type::String),
+            Expression::Ident(ident) => self.types.get(ident.0).copied().ok_or(()),
+            Expression::Call { ident, args } => {
+                let (params, return_type) = match Builtin::from_name(ident.0) {
+                    Some(builtin) => builtin.signature(),
+                    None => {
+                        let (params, return_type) = self.signatures.get(ident.0).ok_or(())?;
+                        (params.as_slice(), *return_type)
+                    }
+                };
+
+                if params.len() != args.len() {
+                    return Err(());
+                }
+
+                for (arg, param) in args.iter().zip(params) {
+                    self.expect_type(arg, *param)?;
+                }
+
+                Ok(return_type)
+            }
+            Expression::Binop { left, op, right } => match op {
+                BinOperation::Equal | BinOperation::NotEqual => {
+                    let left_type = self.expect_value(left)?;
+
+                    // strings have to be compared with `string_equal`
+                    if left_type == Type::String {
+                        return Err(());
+                    }
+
+                    self.expect_type(right, left_type)?;
+                    Ok(Type::Bool)
+                }
+                _ => {
+                    self.expect_type(left, Type::Int)?;
+                    self.expect_type(right, Type::Int)?;
+                    Ok(Type::Int)
+                }
+            },
+            Expression::Unop { right, .. } => {
+                self.expect_type(right, Type::Int)?;
+                Ok(Type::Int)
+            }
+            Expression::Read => Ok(Type::Int),
+            Expression::Result => self.result.ok_or(()),
+            Expression::Length(_) => unreachable!("checked by the unsupported_features analyzer"),
+        }
+    }
+
+    fn type_of_lvalue(&self, lvalue: &LValue<'src>) -> Result<Type, ()> {
+        match lvalue {
+            LValue::Ident(ident) => self.types.get(ident.0).copied().ok_or(()),
+            LValue::LValue(lvalue) => self.type_of_lvalue(lvalue),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(code: &str) -> Result<(), ()> {
+        analyze(&crate::parse(code).unwrap())
+    }
+
+    #[test]
+    fn string_declaration() {
+        assert!(check(r#"int main() { string s = "hello"; return 0; }"#).is_ok());
+    }
+
+    #[test]
+    fn string_assigned_to_int() {
+        assert!(check(r#"int main() { int x = "hello"; return 0; }"#).is_err());
+    }
+
+    #[test]
+    fn char_equality() {
+        assert!(check(r#"int main() { bool b = 'a' == '\n'; return 0; }"#).is_ok());
+    }
+
+    #[test]
+    fn string_equality() {
+        assert!(check(r#"int main() { bool b = "a" == "a"; return 0; }"#).is_err());
+    }
+
+    #[test]
+    fn string_equal_call() {
+        assert!(check(r#"int main() { bool b = string_equal("a", "a"); return 0; }"#).is_ok());
+    }
+
+    #[test]
+    fn mixed_equality() {
+        assert!(check(r#"int main() { bool b = 'a' == 97; return 0; }"#).is_err());
+    }
+
+    #[test]
+    fn call_wrong_arity() {
+        assert!(check(r#"int main() { return string_length("a", "b"); }"#).is_err());
+    }
+
+    #[test]
+    fn compound_assignment_on_char() {
+        assert!(check(r#"int main() { char c = 'a'; c += 1; return 0; }"#).is_err());
+    }
+
+    #[test]
+    fn assert_bool() {
+        assert!(check("int main() { assert(1 == 1); return 0; }").is_ok());
+    }
+
+    #[test]
+    fn assert_int() {
+        assert!(check("int main() { assert(1); return 0; }").is_err());
+    }
+
+    #[test]
+    fn ensures_result() {
+        assert!(check("int main() //@ensures \\result == 0;\n { return 0; }").is_ok());
+    }
+
+    #[test]
+    fn ensures_result_not_bool() {
+        assert!(check("int main() //@ensures \\result;\n { return 0; }").is_err());
+    }
+
+    #[test]
+    fn result_outside_of_ensures() {
+        assert!(check("int main() { //@assert \\result == 0;\n return 0; }").is_err());
+    }
+
+    #[test]
+    fn requires_inside_body() {
+        assert!(check("int main() { //@requires true;\n return 0; }").is_err());
+    }
+
+    #[test]
+    fn void_function() {
+        assert!(check("void f(int x) { return; } int main() { f(1); return 0; }").is_ok());
+    }
+
+    #[test]
+    fn void_function_without_return() {
+        assert!(check("void f() { } int main() { f(); return 0; }").is_ok());
+    }
+
+    #[test]
+    fn void_function_returns_value() {
+        assert!(check("void f() { return 1; } int main() { return 0; }").is_err());
+    }
+
+    #[test]
+    fn return_without_value() {
+        assert!(check("int f() { return; } int main() { return 0; }").is_err());
+    }
+
+    #[test]
+    fn void_variable() {
+        assert!(check("int main() { void x; return 0; }").is_err());
+    }
+
+    #[test]
+    fn void_param() {
+        assert!(check("int f(void x) { return 0; } int main() { return 0; }").is_err());
+    }
+
+    #[test]
+    fn void_call_as_value() {
+        assert!(check("void f() { } int main() { int x = f(); return 0; }").is_err());
+    }
+
+    #[test]
+    fn void_call_compared() {
+        assert!(check("void f() { } int main() { bool b = f() == f(); return 0; }").is_err());
+    }
+
+    #[test]
+    fn result_in_void_function() {
+        assert!(
+            check("void f() //@ensures \\result == 0;\n { } int main() { return 0; }").is_err()
+        );
+    }
+
+    #[test]
+    fn call_with_wrong_argument() {
+        assert!(check("int f(int x) { return x; } int main() { return f(true); }").is_err());
+    }
+
+    #[test]
+    fn duplicate_function() {
+        assert!(check("int main() { return 0; } int main() { return 0; }").is_err());
+    }
+
+    #[test]
+    fn builtin_redefined() {
+        assert!(check("int char_ord(char c) { return 0; } int main() { return 0; }").is_err());
+    }
+
+    #[test]
+    fn missing_main() {
+        assert!(check("int f() { return 0; }").is_err());
+    }
+
+    #[test]
+    fn void_main() {
+        assert!(check("void main() { }").is_err());
+    }
+
+    #[test]
+    fn print_and_read() {
+        assert!(
+            check(r#"int main() { print(read()); print(true); print("hi"); return 0; }"#).is_ok()
+        );
+    }
+
+    #[test]
+    fn print_char() {
+        assert!(check("int main() { print('c'); return 0; }").is_err());
+    }
+
+    #[test]
+    fn print_void() {
+        assert!(check("void f() { } int main() { print(f()); return 0; }").is_err());
+    }
+
+    #[test]
+    fn return_bool() {
+        assert!(check(r#"int main() { return true; }"#).is_err());
+    }
+}
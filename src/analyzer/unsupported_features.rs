@@ -0,0 +1,59 @@
+use crate::grammar::{
+    visit::{self, Visitor},
+    *,
+};
+
+pub fn analyze<'src>(p: &Program<'src>) -> Result<(), ()> {
+    UnsupportedFeatures.visit_program(p)
+}
+
+/// Rejects the parts of the specification language which only make sense with arrays or loops,
+/// which the language doesn't have.
+struct UnsupportedFeatures;
+
+impl<'src> Visitor<'src> for UnsupportedFeatures {
+    type Error = ();
+
+    fn visit_annotation(&mut self, annotation: &Annotation<'src>) -> Result<(), ()> {
+        match annotation {
+            Annotation::LoopInvariant(_) => Err(()),
+            _ => visit::walk_annotation(self, annotation),
+        }
+    }
+
+    fn visit_exp(&mut self, exp: &Expression<'src>) -> Result<(), ()> {
+        match exp {
+            Expression::Length(_) => Err(()),
+            _ => visit::walk_exp(self, exp),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(code: &str) -> Result<(), ()> {
+        analyze(&crate::parse(code).unwrap())
+    }
+
+    #[test]
+    fn length() {
+        assert!(check("int main() //@requires \\length(0) == 0;\n { return 0; }").is_err());
+        assert!(check("int main() { //@assert 1 + \\length(0) == 1;\n return 0; }").is_err());
+    }
+
+    #[test]
+    fn loop_invariant() {
+        assert!(check("int main() { //@loop_invariant true;\n return 0; }").is_err());
+        assert!(check("int main() //@loop_invariant true;\n { return 0; }").is_err());
+    }
+
+    #[test]
+    fn supported_annotations() {
+        assert!(
+            check("int main() //@requires true;\n //@ensures \\result == 0;\n { return 0; }")
+                .is_ok()
+        );
+    }
+}
@@ -1,10 +1,17 @@
 use std::collections::HashSet;
 
-use crate::grammar::*;
+use crate::grammar::{
+    visit::{Visitor, walk_exp, walk_simp},
+    *,
+};
 
 pub fn analyze<'src>(p: &Program<'src>) -> Result<(), ()> {
-    let mut ana = Analyzer::new();
-    ana.analyze(p)
+    for function in &p.0 {
+        // the parameters are visited first, so the contracts of a function can only refer to them
+        Analyzer::new().visit_function(function)?;
+    }
+
+    Ok(())
 }
 
 #[derive(Debug, Default)]
@@ -13,36 +20,28 @@ struct Analyzer<'src> {
     initialised: HashSet<&'src str>,
 }
 
-impl<'src> Analyzer<'src> {
+impl Analyzer<'_> {
     fn new() -> Self {
         Self::default()
     }
+}
 
-    fn analyze(&mut self, p: &Program<'src>) -> Result<(), ()> {
-        self.analyze_statements(&p.0)
-    }
+impl<'src> Visitor<'src> for Analyzer<'src> {
+    type Error = ();
 
-    fn analyze_statements(&mut self, statements: &Statements<'src>) -> Result<(), ()> {
-        match statements {
-            Statements::None => Ok(()),
-            Statements::Statement { current, next } => {
-                self.analyze_statement(current)?;
-                self.analyze_statements(next)
-            }
+    fn visit_param(&mut self, param: &Param<'src>) -> Result<(), ()> {
+        if self.declared.contains(param.ident.0) {
+            return Err(());
         }
-    }
 
-    fn analyze_statement(&mut self, statement: &Statement<'src>) -> Result<(), ()> {
-        match statement {
-            Statement::Decl(decl) => self.analyze_decl(decl),
-            Statement::Simp(simp) => self.analyze_simp(simp),
-            Statement::Return(exp) => self.analyze_exp(exp),
-        }
+        self.declared.insert(param.ident.0);
+        self.initialised.insert(param.ident.0);
+        Ok(())
     }
 
-    fn analyze_decl(&mut self, decl: &Declaration<'src>) -> Result<(), ()> {
+    fn visit_decl(&mut self, decl: &Declaration<'src>) -> Result<(), ()> {
         match decl {
-            Declaration::Ident(ident) => {
+            Declaration::Ident { ident, .. } => {
                 if self.declared.contains(ident.0) {
                     return Err(());
                 }
@@ -50,30 +49,20 @@ impl<'src> Analyzer<'src> {
                 self.declared.insert(ident.0);
                 Ok(())
             }
-            Declaration::IdentExp { ident, exp } => {
+            Declaration::IdentExp { ident, exp, .. } => {
                 if self.declared.contains(ident.0) {
                     return Err(());
                 }
 
                 self.declared.insert(ident.0);
                 self.initialised.insert(ident.0);
-                self.analyze_exp(exp)
+                self.visit_exp(exp)
             }
         }
     }
 
-    fn analyze_simp(&mut self, simp: &SimpleInstruction<'src>) -> Result<(), ()> {
-        let SimpleInstruction { lvalue, exp, .. } = simp;
-
-        self.analyze_lvalue(lvalue)?;
-        self.analyze_exp(exp)?;
-
-        Ok(())
-    }
-
-    fn analyze_exp(&mut self, exp: &Expression<'src>) -> Result<(), ()> {
+    fn visit_exp(&mut self, exp: &Expression<'src>) -> Result<(), ()> {
         match exp {
-            Expression::NestedExp(nested_exp) => self.analyze_exp(nested_exp),
             Expression::Ident(ident) => {
                 if !self.initialised.contains(ident.0) {
                     return Err(());
@@ -81,17 +70,29 @@ impl<'src> Analyzer<'src> {
 
                 Ok(())
             }
-            Expression::Binop { left, right, .. } => {
-                self.analyze_exp(left)?;
-                self.analyze_exp(right)?;
-                Ok(())
+            _ => walk_exp(self, exp),
+        }
+    }
+
+    fn visit_simp(&mut self, simp: &SimpleInstruction<'src>) -> Result<(), ()> {
+        walk_simp(self, simp)?;
+
+        // a compound assignment reads the variable as well
+        let mut lvalue = &simp.lvalue;
+        while let LValue::LValue(inner) = lvalue {
+            lvalue = inner;
+        }
+        match lvalue {
+            LValue::Ident(ident)
+                if simp.asnop != AsNop::Equal && !self.initialised.contains(ident.0) =>
+            {
+                Err(())
             }
-            Expression::Unop { right, .. } => self.analyze_exp(right),
             _ => Ok(()),
         }
     }
 
-    fn analyze_lvalue(&mut self, lvalue: &LValue<'src>) -> Result<(), ()> {
+    fn visit_lvalue(&mut self, lvalue: &LValue<'src>) -> Result<(), ()> {
         match lvalue {
             LValue::Ident(ident) => {
                 if !self.declared.contains(ident.0) {
@@ -99,7 +100,24 @@ impl<'src> Analyzer<'src> {
                 }
                 Ok(())
             }
-            LValue::LValue(lvalue) => self.analyze_lvalue(lvalue),
+            LValue::LValue(lvalue) => self.visit_lvalue(lvalue),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(code: &str) -> Result<(), ()> {
+        analyze(&crate::parse(code).unwrap())
+    }
+
+    #[test]
+    fn compound_assignment_reads_the_variable() {
+        assert!(check("int main() { int x; x += 1; return 0; }").is_err());
+        assert!(check("int main() { int x; (x) %= 2; return 0; }").is_err());
+        assert!(check("int main() { int x; x = 1; return 0; }").is_ok());
+        assert!(check("int main() { int x = 1; x *= 2; return x; }").is_ok());
+    }
+}
@@ -0,0 +1,368 @@
+//! The command line arguments of the compiler.
+
+use std::{fmt, path::PathBuf, time::Duration};
+
+pub const USAGE: &str = "\
+usage: pimpf [options] <input> [<output>]
+       pimpf test [--differential] [--timeout=<seconds>] <dir>
+       pimpf fmt [--check] <input>...
+
+Compiles the C0 program in <input>, which is read from stdin if it's `-`.
+
+options:
+    --emit=<stage>       what to write: tokens, ast, ir, asm, obj or exe (default: exe)
+    -o <path>            where to write it, `-` is stdout. Defaults to stdout for the textual
+                         stages, to <input> with the extension `.o` for obj and to `a.out` for exe
+    --interpret          run the program with the interpreter instead of compiling it
+    -d                   check the annotations at runtime
+    --no-gc              never free strings
+    --time-passes        print how long every pass took to stderr
+    --dump-after=<pass>  print the program to stderr after the pass
+    -h, --help           print this help
+
+`pimpf test` compiles and runs every test file in <dir> and checks the expectation in its
+`//test` header. Compiling and running each may take 10 seconds unless `--timeout` says otherwise.
+With `--differential` every program is run by the interpreter and compiled instead, and the first
+program on which they differ is minimised.
+
+`pimpf fmt` rewrites the inputs in the canonical style and keeps their comments, stdin is written
+to stdout. With `--check` it only lists the inputs which aren't formatted and fails if there are
+any.";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    Tokens,
+    Ast,
+    Ir,
+    Asm,
+    Obj,
+    Exe,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Output {
+    Stdout,
+    File(PathBuf),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cli {
+    pub input: Input,
+    pub output: Output,
+    pub emit: Emit,
+    pub interpret: bool,
+    pub dynamic_checks: bool,
+    pub garbage_collection: bool,
+    pub time_passes: bool,
+    pub dump_after: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Compile(Cli),
+    /// Runs the tests in the directory with the given timeout.
+    Test {
+        dir: PathBuf,
+        timeout: Duration,
+        /// Compare the interpreter with the native backend instead of checking the expectations.
+        differential: bool,
+    },
+    /// Formats the inputs, or only checks that they are formatted.
+    Fmt {
+        inputs: Vec<Input>,
+        check: bool,
+    },
+    Help,
+}
+
+/// Parses the arguments without the name of the executable.
+/// Returns a message for the user if they don't make sense.
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+    if args.next_if(|arg| arg == "test").is_some() {
+        return parse_test(args);
+    }
+    if args.next_if(|arg| arg == "fmt").is_some() {
+        return parse_fmt(args);
+    }
+
+    let mut inputs = Vec::new();
+    let mut output = None;
+    let mut emit = None;
+    let mut interpret = false;
+    let mut dynamic_checks = false;
+    let mut garbage_collection = true;
+    let mut time_passes = false;
+    let mut dump_after = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" => {
+                let path = args.next().ok_or("`-o` needs a path")?;
+                if output.replace(path).is_some() {
+                    return Err("the output is given more than once".to_string());
+                }
+            }
+            "--interpret" => interpret = true,
+            "-d" => dynamic_checks = true,
+            "--no-gc" => garbage_collection = false,
+            "--time-passes" => time_passes = true,
+            "-" => inputs.push(arg),
+            _ => {
+                if let Some(stage) = arg.strip_prefix("--emit=") {
+                    emit = Some(Emit::from_name(stage).ok_or_else(|| {
+                        format!(
+                            "unknown stage `{}`, expected tokens, ast, ir, asm, obj or exe",
+                            stage
+                        )
+                    })?);
+                } else if let Some(pass) = arg.strip_prefix("--dump-after=") {
+                    dump_after = Some(pass.to_string());
+                } else if arg.starts_with('-') {
+                    return Err(format!("unknown option `{}`", arg));
+                } else {
+                    inputs.push(arg);
+                }
+            }
+        }
+    }
+
+    let mut inputs = inputs.into_iter();
+    let input = match inputs.next().ok_or("no input file given")?.as_str() {
+        "-" => Input::Stdin,
+        path => Input::File(path.into()),
+    };
+    // the output may be given as a second positional argument as well
+    if let Some(path) = inputs.next()
+        && output.replace(path).is_some()
+    {
+        return Err("the output is given more than once".to_string());
+    }
+    if let Some(arg) = inputs.next() {
+        return Err(format!("unexpected argument `{}`", arg));
+    }
+
+    if interpret && emit.is_some() {
+        return Err("`--interpret` can't be combined with `--emit`".to_string());
+    }
+    let emit = emit.unwrap_or(Emit::Exe);
+
+    let output = match output.as_deref() {
+        Some("-") => Output::Stdout,
+        Some(path) => Output::File(path.into()),
+        None => match (emit, &input) {
+            (Emit::Obj, Input::File(path)) => Output::File(path.with_extension("o")),
+            (Emit::Obj, Input::Stdin) => Output::File("a.o".into()),
+            (Emit::Exe, _) => Output::File("a.out".into()),
+            _ => Output::Stdout,
+        },
+    };
+    if output == Output::Stdout && matches!(emit, Emit::Obj | Emit::Exe) && !interpret {
+        return Err("binary stages can't be written to stdout".to_string());
+    }
+
+    Ok(Command::Compile(Cli {
+        input,
+        output,
+        emit,
+        interpret,
+        dynamic_checks,
+        garbage_collection,
+        time_passes,
+        dump_after,
+    }))
+}
+
+fn parse_test(args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut dir = None;
+    let mut timeout = Duration::from_secs(10);
+    let mut differential = false;
+
+    for arg in args {
+        if arg == "-h" || arg == "--help" {
+            return Ok(Command::Help);
+        } else if arg == "--differential" {
+            differential = true;
+        } else if let Some(seconds) = arg.strip_prefix("--timeout=") {
+            timeout = seconds
+                .parse()
+                .ok()
+                .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+                .ok_or_else(|| format!("invalid timeout `{}`", seconds))?;
+        } else if arg.starts_with('-') {
+            return Err(format!("unknown option `{}`", arg));
+        } else if dir.replace(PathBuf::from(&arg)).is_some() {
+            return Err(format!("unexpected argument `{}`", arg));
+        }
+    }
+
+    let dir = dir.ok_or("no test directory given")?;
+    Ok(Command::Test {
+        dir,
+        timeout,
+        differential,
+    })
+}
+
+impl Emit {
+    fn from_name(name: &str) -> Option<Self> {
+        let emit = match name {
+            "tokens" => Emit::Tokens,
+            "ast" => Emit::Ast,
+            "ir" => Emit::Ir,
+            "asm" => Emit::Asm,
+            "obj" => Emit::Obj,
+            "exe" => Emit::Exe,
+            _ => return None,
+        };
+
+        Some(emit)
+    }
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Input::Stdin => write!(f, "<stdin>"),
+            Input::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+fn parse_fmt(args: impl Iterator<Item = String>) -> Result<Command, String> {
+    let mut inputs = Vec::new();
+    let mut check = false;
+
+    for arg in args {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--check" => check = true,
+            "-" => inputs.push(Input::Stdin),
+            _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+            path => inputs.push(Input::File(path.into())),
+        }
+    }
+
+    if inputs.is_empty() {
+        return Err("no input file given".to_string());
+    }
+    Ok(Command::Fmt { inputs, check })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &str) -> Result<Command, String> {
+        parse(args.split_whitespace().map(String::from))
+    }
+
+    fn compile(args: &str) -> Cli {
+        match parse_args(args) {
+            Ok(Command::Compile(cli)) => cli,
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn defaults() {
+        assert_eq!(
+            compile("main.c0"),
+            Cli {
+                input: Input::File("main.c0".into()),
+                output: Output::File("a.out".into()),
+                emit: Emit::Exe,
+                interpret: false,
+                dynamic_checks: false,
+                garbage_collection: true,
+                time_passes: false,
+                dump_after: None,
+            }
+        );
+    }
+
+    #[test]
+    fn emit() {
+        let cli = compile("--emit=asm - -d");
+        assert_eq!(cli.emit, Emit::Asm);
+        assert_eq!(cli.input, Input::Stdin);
+        assert_eq!(cli.output, Output::Stdout);
+        assert!(cli.dynamic_checks);
+
+        assert_eq!(
+            compile("--emit=obj dir/main.c0").output,
+            Output::File("dir/main.o".into())
+        );
+        assert_eq!(
+            compile("--emit=ir main.c0 -o out.ir").output,
+            Output::File("out.ir".into())
+        );
+    }
+
+    #[test]
+    fn positional_output() {
+        assert_eq!(compile("main.c0 main").output, Output::File("main".into()));
+    }
+
+    #[test]
+    fn help() {
+        assert_eq!(parse_args("main.c0 --help"), Ok(Command::Help));
+    }
+
+    #[test]
+    fn test() {
+        assert_eq!(
+            parse_args("test tests --timeout=0.5"),
+            Ok(Command::Test {
+                dir: "tests".into(),
+                timeout: Duration::from_millis(500),
+                differential: false,
+            })
+        );
+        assert_eq!(
+            parse_args("test --differential tests"),
+            Ok(Command::Test {
+                dir: "tests".into(),
+                timeout: Duration::from_secs(10),
+                differential: true,
+            })
+        );
+        assert!(parse_args("test").is_err());
+        assert!(parse_args("test a b").is_err());
+        assert!(parse_args("test a --timeout=-1").is_err());
+        // only the first argument is a subcommand
+        assert_eq!(compile("main.c0 test").output, Output::File("test".into()));
+    }
+
+    #[test]
+    fn fmt() {
+        assert_eq!(
+            parse_args("fmt --check a.c0 -"),
+            Ok(Command::Fmt {
+                inputs: vec![Input::File("a.c0".into()), Input::Stdin],
+                check: true,
+            })
+        );
+        assert!(parse_args("fmt").is_err());
+        assert!(parse_args("fmt --emit=ast a.c0").is_err());
+    }
+
+    #[test]
+    fn usage_errors() {
+        assert!(parse_args("").is_err());
+        assert!(parse_args("main.c0 -o").is_err());
+        assert!(parse_args("main.c0 -o a -o b").is_err());
+        assert!(parse_args("main.c0 a b").is_err());
+        assert!(parse_args("main.c0 --emit=llvm").is_err());
+        assert!(parse_args("main.c0 --emit=exe -o -").is_err());
+        assert!(parse_args("main.c0 --emit=ast --interpret").is_err());
+        assert!(parse_args("main.c0 --verbose").is_err());
+    }
+}
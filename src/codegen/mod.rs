@@ -0,0 +1,313 @@
+//! Generates x86-64 assembly in AT&T syntax for Linux from the IR.
+//!
+//! There's no register allocation yet: every variable lives in its own 8 byte stack slot and the
+//! instructions only use scratch registers. Ints only use the lower 32 bits of their slot.
+//! The runtime in `runtime.c` provides the library functions and the `main` which calls `c0_main`.
+pub mod toolchain;
+
+use std::fmt::Write;
+
+use crate::ir::*;
+
+const ARGUMENT_REGISTERS: [&str; 6] = ["%rdi", "%rsi", "%rdx", "%rcx", "%r8", "%r9"];
+
+/// Writes a line of the text section.
+macro_rules! emit {
+    ($generator:expr, $($arg:tt)*) => {
+        let _ = writeln!($generator.text, "    {}", format_args!($($arg)*));
+    };
+}
+
+pub fn asm(program: &Program<'_>) -> String {
+    let mut generator = Generator::default();
+    for function in &program.functions {
+        generator.function(function);
+    }
+
+    let mut asm = String::from("    .text\n");
+    asm += &generator.text;
+    if !generator.rodata.is_empty() {
+        asm += "\n    .section .rodata\n";
+        asm += &generator.rodata;
+    }
+    // the stack doesn't have to be executable
+    asm += "\n    .section .note.GNU-stack,\"\",@progbits\n";
+
+    asm
+}
+
+#[derive(Debug, Default)]
+struct Generator {
+    text: String,
+    /// The string literals.
+    rodata: String,
+    strings: usize,
+}
+
+impl Generator {
+    fn function(&mut self, function: &Function<'_>) {
+        let name = symbol(function.name);
+        let _ = writeln!(self.text, "\n    .globl {}", name);
+        emit!(self, ".type {}, @function", name);
+        let _ = writeln!(self.text, "{}:", name);
+
+        emit!(self, "pushq %rbp");
+        emit!(self, "movq %rsp, %rbp");
+        // calls need a stack which is aligned to 16 bytes
+        let frame_size = (8 * function.var_count as usize).next_multiple_of(16);
+        if frame_size > 0 {
+            emit!(self, "subq ${}, %rsp", frame_size);
+        }
+
+        for (index, param) in function.params.iter().enumerate() {
+            match ARGUMENT_REGISTERS.get(index) {
+                Some(register) => {
+                    emit!(self, "movq {}, {}", register, slot(*param));
+                }
+                None => {
+                    // above the return address and the saved %rbp
+                    let offset = 16 + 8 * (index - ARGUMENT_REGISTERS.len());
+                    emit!(self, "movq {}(%rbp), %rax", offset);
+                    emit!(self, "movq %rax, {}", slot(*param));
+                }
+            }
+        }
+
+        for (id, block) in function.blocks.iter().enumerate() {
+            let _ = writeln!(self.text, "{}:", label(function, BlockId(id)));
+            for instruction in &block.instructions {
+                self.instruction(instruction);
+            }
+            self.terminator(function, BlockId(id), &block.terminator);
+        }
+    }
+
+    fn instruction(&mut self, instruction: &Instruction<'_>) {
+        match instruction {
+            Instruction::Copy { dest, src } => {
+                self.load(src, "%rax");
+                self.store(*dest);
+            }
+            Instruction::Binary {
+                dest,
+                op,
+                left,
+                right,
+            } => {
+                self.load(left, "%rax");
+                self.load(right, "%rcx");
+                match op {
+                    BinOp::Add => {
+                        emit!(self, "addl %ecx, %eax");
+                    }
+                    BinOp::Sub => {
+                        emit!(self, "subl %ecx, %eax");
+                    }
+                    BinOp::Mul => {
+                        emit!(self, "imull %ecx, %eax");
+                    }
+                    // `idivl` raises `SIGFPE` for the same operands as C0
+                    BinOp::Div => {
+                        emit!(self, "cltd");
+                        emit!(self, "idivl %ecx");
+                    }
+                    BinOp::Mod => {
+                        emit!(self, "cltd");
+                        emit!(self, "idivl %ecx");
+                        emit!(self, "movl %edx, %eax");
+                    }
+                    BinOp::Eq | BinOp::Ne => {
+                        let set = match op {
+                            BinOp::Eq => "sete",
+                            _ => "setne",
+                        };
+                        emit!(self, "cmpl %ecx, %eax");
+                        emit!(self, "{} %al", set);
+                        emit!(self, "movzbl %al, %eax");
+                    }
+                    BinOp::Shl => {
+                        emit!(self, "shll %cl, %eax");
+                    }
+                    BinOp::Sar => {
+                        emit!(self, "sarl %cl, %eax");
+                    }
+                    BinOp::Shr => {
+                        emit!(self, "shrl %cl, %eax");
+                    }
+                    // the product is in `%edx:%eax`
+                    BinOp::MulHigh => {
+                        emit!(self, "imull %ecx");
+                        emit!(self, "movl %edx, %eax");
+                    }
+                }
+                self.store(*dest);
+            }
+            Instruction::String { dest, value } => {
+                let label = format!(".Lstr{}", self.strings);
+                self.strings += 1;
+
+                let _ = writeln!(self.rodata, "    .p2align 3\n{}:", label);
+                let _ = writeln!(self.rodata, "    .quad {}", value.len());
+                let bytes: Vec<String> = value.iter().chain([&0]).map(u8::to_string).collect();
+                let _ = writeln!(self.rodata, "    .byte {}", bytes.join(", "));
+
+                emit!(self, "leaq {}(%rip), %rax", label);
+                self.store(*dest);
+            }
+            Instruction::Call { dest, callee, args } => {
+                let symbol = match callee {
+                    Callee::Function(name) => symbol(name),
+                    Callee::Builtin(builtin) => format!("c0rt_{}", builtin.name()),
+                };
+                self.call(&symbol, args);
+                if let Some(dest) = dest {
+                    self.store(*dest);
+                }
+            }
+            Instruction::Print { kind, value } => {
+                let symbol = match kind {
+                    PrintKind::Int => "c0rt_print_int",
+                    PrintKind::Bool => "c0rt_print_bool",
+                    PrintKind::String => "c0rt_print_string",
+                };
+                self.call(symbol, std::slice::from_ref(value));
+            }
+            Instruction::Read { dest } => {
+                self.call("c0rt_read", &[]);
+                self.store(*dest);
+            }
+            Instruction::Phi { .. } => unreachable!("phis are removed when leaving SSA form"),
+        }
+    }
+
+    fn terminator(&mut self, function: &Function<'_>, id: BlockId, terminator: &Terminator) {
+        match terminator {
+            Terminator::Return(value) => {
+                if let Some(value) = value {
+                    self.load(value, "%rax");
+                }
+                emit!(self, "leave");
+                emit!(self, "ret");
+            }
+            Terminator::Jump(target) => {
+                if target.0 != id.0 + 1 {
+                    emit!(self, "jmp {}", label(function, *target));
+                }
+            }
+            Terminator::Branch {
+                cond,
+                then,
+                otherwise,
+            } => {
+                self.load(cond, "%rax");
+                emit!(self, "testl %eax, %eax");
+                emit!(self, "jne {}", label(function, *then));
+                if otherwise.0 != id.0 + 1 {
+                    emit!(self, "jmp {}", label(function, *otherwise));
+                }
+            }
+            Terminator::Abort => {
+                emit!(self, "call c0rt_abort");
+            }
+            Terminator::Unreachable => {
+                emit!(self, "ud2");
+            }
+        }
+    }
+
+    /// Calls the function with the System V calling convention, the result is in `%rax`.
+    fn call(&mut self, symbol: &str, args: &[Operand]) {
+        let stack_args = args.len().saturating_sub(ARGUMENT_REGISTERS.len());
+        let padding = stack_args % 2;
+        if padding == 1 {
+            emit!(self, "subq $8, %rsp");
+        }
+
+        for arg in args.iter().skip(ARGUMENT_REGISTERS.len()).rev() {
+            self.load(arg, "%rax");
+            emit!(self, "pushq %rax");
+        }
+        for (arg, register) in args.iter().zip(ARGUMENT_REGISTERS) {
+            self.load(arg, register);
+        }
+
+        emit!(self, "call {}", symbol);
+        if stack_args > 0 {
+            emit!(self, "addq ${}, %rsp", 8 * (stack_args + padding));
+        }
+    }
+
+    fn load(&mut self, operand: &Operand, register: &str) {
+        match operand {
+            Operand::Const(value) => {
+                emit!(self, "movq ${}, {}", value, register);
+            }
+            Operand::Var(var) => {
+                emit!(self, "movq {}, {}", slot(*var), register);
+            }
+        }
+    }
+
+    /// Stores `%rax` in the slot of the variable.
+    fn store(&mut self, var: Var) {
+        emit!(self, "movq %rax, {}", slot(var));
+    }
+}
+
+/// C0 functions get a prefix, so they can't clash with the runtime or the C library.
+fn symbol(function: &str) -> String {
+    format!("c0_{}", function)
+}
+
+/// Identifiers can't contain a `.`, so the labels are unique.
+fn label(function: &Function<'_>, block: BlockId) -> String {
+    format!(".L{}.{}", function.name, block)
+}
+
+fn slot(var: Var) -> String {
+    format!("{}(%rbp)", -8 * (var.0 as i64 + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn function() {
+        let program = crate::parse("int main() { int x = 3; return x / 2; }").unwrap();
+        let asm = asm(&lower::lower(&program, false));
+
+        assert!(asm.contains(
+            "
+    .globl c0_main
+    .type c0_main, @function
+c0_main:
+    pushq %rbp
+    movq %rsp, %rbp
+    subq $16, %rsp
+.Lmain.b0:
+    movq $3, %rax
+    movq %rax, -8(%rbp)
+    movq -8(%rbp), %rax
+    movq $2, %rcx
+    cltd
+    idivl %ecx
+    movq %rax, -16(%rbp)
+    movq -16(%rbp), %rax
+    leave
+    ret
+.Lmain.b1:
+    ud2
+"
+        ));
+    }
+
+    #[test]
+    fn string_literal() {
+        let program = crate::parse(r#"int main() { print("hi"); return 0; }"#).unwrap();
+        let asm = asm(&lower::lower(&program, false));
+
+        assert!(asm.contains("leaq .Lstr0(%rip), %rax"));
+        assert!(asm.contains(".Lstr0:\n    .quad 2\n    .byte 104, 105, 0\n"));
+    }
+}
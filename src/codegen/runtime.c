@@ -0,0 +1,271 @@
+/*
+ * The runtime which is linked into every compiled C0 program.
+ *
+ * It provides `print`, `read`, the string library and a conservative mark-and-sweep garbage
+ * collector for strings. Compiled functions keep every value in a stack slot, so scanning the
+ * stack finds every string which is still reachable. Defining `C0_NO_GC` keeps every string alive.
+ */
+#include <setjmp.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+/* The layout of string literals which are emitted by the compiler as well. */
+struct c0_string {
+    int64_t length;
+    /* followed by a `\0` which isn't part of the string */
+    char bytes[];
+};
+
+/* The heap doesn't collect before this many bytes have been allocated. */
+#define MIN_THRESHOLD (1 << 16)
+
+struct object {
+    size_t size;
+    int marked;
+    struct c0_string string;
+};
+
+static struct object **objects;
+static size_t object_count;
+static size_t object_capacity;
+static size_t allocated_bytes;
+static size_t threshold = MIN_THRESHOLD;
+static uintptr_t *stack_bottom;
+
+int c0_main(void);
+
+static int compare_objects(const void *left, const void *right) {
+    uintptr_t l = (uintptr_t) * (struct object *const *)left;
+    uintptr_t r = (uintptr_t) * (struct object *const *)right;
+    return (l > r) - (l < r);
+}
+
+/* Marks the object containing the address, interior pointers count as well. */
+static void mark(uintptr_t address) {
+    size_t low = 0;
+    size_t high = object_count;
+    while (low < high) {
+        size_t middle = low + (high - low) / 2;
+        uintptr_t start = (uintptr_t)objects[middle];
+        if (address < start) {
+            high = middle;
+        } else if (address >= start + objects[middle]->size) {
+            low = middle + 1;
+        } else {
+            objects[middle]->marked = 1;
+            return;
+        }
+    }
+}
+
+/* Kept out of line, so the registers spilled by `setjmp` are below the frame of the caller. */
+static __attribute__((noinline)) void scan_stack(void) {
+    uintptr_t *top = (uintptr_t *)__builtin_frame_address(0);
+    for (uintptr_t *word = top; word < stack_bottom; word++) {
+        mark(*word);
+    }
+}
+
+static void collect(void) {
+    /* spills the callee saved registers onto the stack */
+    jmp_buf registers;
+    setjmp(registers);
+
+    qsort(objects, object_count, sizeof(struct object *), compare_objects);
+    scan_stack();
+
+    size_t kept = 0;
+    allocated_bytes = 0;
+    for (size_t i = 0; i < object_count; i++) {
+        if (objects[i]->marked) {
+            objects[i]->marked = 0;
+            allocated_bytes += objects[i]->size;
+            objects[kept++] = objects[i];
+        } else {
+            free(objects[i]);
+        }
+    }
+    object_count = kept;
+
+    threshold = 2 * allocated_bytes > MIN_THRESHOLD ? 2 * allocated_bytes : MIN_THRESHOLD;
+}
+
+static struct c0_string *alloc_string(int64_t length) {
+#ifndef C0_NO_GC
+    if (allocated_bytes >= threshold) {
+        collect();
+    }
+#endif
+
+    if (object_count == object_capacity) {
+        object_capacity = object_capacity ? 2 * object_capacity : 1024;
+        objects = realloc(objects, object_capacity * sizeof(struct object *));
+        if (!objects) {
+            abort();
+        }
+    }
+
+    size_t size = sizeof(struct object) + (size_t)length + 1;
+    struct object *object = calloc(1, size);
+    if (!object) {
+        abort();
+    }
+
+    object->size = size;
+    object->string.length = length;
+    objects[object_count++] = object;
+    allocated_bytes += size;
+
+    return &object->string;
+}
+
+/* Converts `index` into an index of a string with the given length, the length itself is valid. */
+static int64_t checked_index(int32_t index, int64_t length) {
+    if (index < 0 || index > length) {
+        abort();
+    }
+
+    return index;
+}
+
+void c0rt_abort(void) { abort(); }
+
+/* The process might get killed by a signal afterwards, so every print is flushed. */
+void c0rt_print_int(int32_t value) {
+    printf("%d\n", value);
+    fflush(stdout);
+}
+
+void c0rt_print_bool(int32_t value) {
+    puts(value ? "true" : "false");
+    fflush(stdout);
+}
+
+void c0rt_print_string(struct c0_string *string) {
+    fwrite(string->bytes, 1, (size_t)string->length, stdout);
+    putchar('\n');
+    fflush(stdout);
+}
+
+/* Reads the next whitespace separated int, aborts if there's none. */
+int32_t c0rt_read(void) {
+    int c;
+    do {
+        c = getchar();
+    } while (c == ' ' || c == '\t' || c == '\n' || c == '\v' || c == '\f' || c == '\r');
+
+    int negative = 0;
+    if (c == '-' || c == '+') {
+        negative = c == '-';
+        c = getchar();
+    }
+
+    int64_t value = 0;
+    int digits = 0;
+    while (c >= '0' && c <= '9') {
+        value = 10 * value + (c - '0');
+        if (value > (int64_t)INT32_MAX + 1) {
+            abort();
+        }
+        digits++;
+        c = getchar();
+    }
+
+    if (c != EOF && c != ' ' && c != '\t' && c != '\n' && c != '\v' && c != '\f' && c != '\r') {
+        abort();
+    }
+    ungetc(c, stdin);
+
+    if (negative) {
+        value = -value;
+    }
+    if (!digits || value > INT32_MAX) {
+        abort();
+    }
+
+    return (int32_t)value;
+}
+
+int32_t c0rt_string_length(struct c0_string *string) { return (int32_t)string->length; }
+
+int32_t c0rt_string_charat(struct c0_string *string, int32_t index) {
+    if (checked_index(index, string->length) == string->length) {
+        abort();
+    }
+
+    return (unsigned char)string->bytes[index];
+}
+
+struct c0_string *c0rt_string_join(struct c0_string *left, struct c0_string *right) {
+    struct c0_string *joined = alloc_string(left->length + right->length);
+    memcpy(joined->bytes, left->bytes, (size_t)left->length);
+    memcpy(joined->bytes + left->length, right->bytes, (size_t)right->length);
+
+    return joined;
+}
+
+struct c0_string *c0rt_string_sub(struct c0_string *string, int32_t start, int32_t end) {
+    int64_t from = checked_index(start, string->length);
+    int64_t to = checked_index(end, string->length);
+    if (from > to) {
+        abort();
+    }
+
+    struct c0_string *sub = alloc_string(to - from);
+    memcpy(sub->bytes, string->bytes + from, (size_t)(to - from));
+
+    return sub;
+}
+
+int32_t c0rt_string_compare(struct c0_string *left, struct c0_string *right) {
+    int64_t length = left->length < right->length ? left->length : right->length;
+    int ordering = memcmp(left->bytes, right->bytes, (size_t)length);
+    if (ordering == 0) {
+        ordering = (left->length > right->length) - (left->length < right->length);
+    }
+
+    return (ordering > 0) - (ordering < 0);
+}
+
+int32_t c0rt_string_equal(struct c0_string *left, struct c0_string *right) {
+    return c0rt_string_compare(left, right) == 0;
+}
+
+struct c0_string *c0rt_string_fromint(int32_t value) {
+    char buffer[16];
+    int length = snprintf(buffer, sizeof(buffer), "%d", value);
+
+    struct c0_string *string = alloc_string(length);
+    memcpy(string->bytes, buffer, (size_t)length);
+
+    return string;
+}
+
+struct c0_string *c0rt_string_fromchar(int32_t c) {
+    if (c == '\0') {
+        abort();
+    }
+
+    struct c0_string *string = alloc_string(1);
+    string->bytes[0] = (char)c;
+
+    return string;
+}
+
+int32_t c0rt_char_ord(int32_t c) { return c; }
+
+int32_t c0rt_char_chr(int32_t ord) {
+    if (ord < 0 || ord > 127) {
+        abort();
+    }
+
+    return ord;
+}
+
+int main(void) {
+    stack_bottom = (uintptr_t *)__builtin_frame_address(0);
+
+    return c0_main();
+}
@@ -0,0 +1,221 @@
+//! Assembles and links the generated assembly with the C compiler of the system, which is taken
+//! from `$CC` and defaults to `cc`.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+const RUNTIME: &str = include_str!("runtime.c");
+
+/// Turns the assembly into an object file which still has to be linked with the runtime.
+pub fn assemble(asm: &str, output: &Path) -> Result<(), String> {
+    let mut command = compiler();
+    command
+        .args(["-c", "-x", "assembler", "-", "-o"])
+        .arg(output);
+
+    run(command, asm)
+}
+
+/// Links the assembly with the runtime into an executable.
+/// Strings are never freed if `garbage_collection` is disabled.
+pub fn link(asm: &str, output: &Path, garbage_collection: bool) -> Result<(), String> {
+    let dir = TempDir::new().map_err(|error| format!("couldn't create a directory: {}", error))?;
+    let object = dir.path.join("program.o");
+    assemble(asm, &object)?;
+    let runtime = dir.path.join("runtime.c");
+    std::fs::write(&runtime, RUNTIME)
+        .map_err(|error| format!("couldn't write the runtime: {}", error))?;
+
+    let mut command = compiler();
+    command
+        .arg("-O2")
+        .arg(&object)
+        .arg(&runtime)
+        .arg("-o")
+        .arg(output);
+    if !garbage_collection {
+        command.arg("-DC0_NO_GC");
+    }
+
+    run(command, "")
+}
+
+fn compiler() -> Command {
+    Command::new(std::env::var_os("CC").unwrap_or_else(|| "cc".into()))
+}
+
+/// Runs the compiler with the assembly, if any, as its stdin. Its errors are passed through to
+/// stderr.
+fn run(mut command: Command, asm: &str) -> Result<(), String> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|error| format!("couldn't run `{}`: {}", program, error))?;
+
+    let written = child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(asm.as_bytes());
+    let status = child
+        .wait()
+        .map_err(|error| format!("couldn't run `{}`: {}", program, error))?;
+    written.map_err(|error| format!("couldn't pass the assembly to `{}`: {}", program, error))?;
+
+    match status.success() {
+        true => Ok(()),
+        false => Err(format!("`{}` failed with {}", program, status)),
+    }
+}
+
+/// A directory which is removed again once it's dropped.
+pub struct TempDir {
+    pub path: PathBuf,
+}
+
+impl TempDir {
+    pub fn new() -> std::io::Result<Self> {
+        // several directories might be used at once, e.g. by the tests
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        let path = std::env::temp_dir().join(format!(
+            "pimpf-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(&path)?;
+
+        Ok(Self { path })
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+
+    use super::*;
+    use crate::{
+        codegen, interpreter,
+        passes::{Options, PassManager},
+    };
+
+    /// Compiles and runs the program, returns its exit code or signal and its output.
+    fn run_code(code: &str, input: &str) -> (Result<i32, i32>, String) {
+        let program = crate::parse(code).unwrap();
+        let ir = PassManager::compiler(Options::default())
+            .run(&program)
+            .unwrap()
+            .unwrap();
+
+        let dir = TempDir::new().unwrap();
+        let executable = dir.path.join("program");
+        link(&codegen::asm(&ir), &executable, true).unwrap();
+
+        let mut child = Command::new(&executable)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        let status = output
+            .status
+            .code()
+            .ok_or_else(|| output.status.signal().unwrap());
+        (status, String::from_utf8(output.stdout).unwrap())
+    }
+
+    #[test]
+    fn exit_code() {
+        assert_eq!(
+            run_code("int main() { int x = 6; x *= 7; return x; }", ""),
+            (Ok(42), String::new())
+        );
+    }
+
+    #[test]
+    fn calls() {
+        let code = "int f(int a, int b, int c, int d, int e, int g, int h, int i) {
+                return a - b + c - d + e - g + h * i;
+            }
+            int main() { print(f(1, 2, 3, 4, 5, 6, 7, 8)); return 0; }";
+
+        assert_eq!(run_code(code, ""), (Ok(0), "53\n".to_string()));
+    }
+
+    #[test]
+    fn io() {
+        let code = r#"int main() {
+                int x = read();
+                print(x == -3);
+                print(string_join("x: ", string_fromint(x)));
+                return 0;
+            }"#;
+
+        assert_eq!(
+            run_code(code, "  -3\n"),
+            (Ok(0), "true\nx: -3\n".to_string())
+        );
+        assert_eq!(run_code(code, "3x").0, Err(6));
+    }
+
+    #[test]
+    fn read_skips_the_whitespace_of_the_interpreter() {
+        let code = "int main() { return read() - read(); }";
+        let input = "\x0b7\x0b\x0c-2\x0b";
+
+        let interpreted = interpreter::interpret_with_io(
+            &crate::parse(code).unwrap(),
+            interpreter::Options::default(),
+            &mut input.as_bytes(),
+            &mut Vec::new(),
+        );
+        assert_eq!(interpreted, Ok(9));
+        assert_eq!(run_code(code, input).0, Ok(9));
+    }
+
+    #[test]
+    fn signals() {
+        assert_eq!(
+            run_code("int main() { print(1); return 1 / (1 - 1); }", ""),
+            (Err(8), "1\n".to_string())
+        );
+        assert_eq!(
+            run_code("int main() { int x = -2147483648; return x % -1; }", "").0,
+            Err(8)
+        );
+        assert_eq!(
+            run_code("int main() { assert(1 == 2); return 0; }", "").0,
+            Err(6)
+        );
+    }
+
+    #[test]
+    fn garbage_collection() {
+        let mut code = String::from(r#"int main() { string s = "abcdefghijklmnop"; string t = s;"#);
+        // allocates far more than the threshold of the collector
+        for _ in 0..14 {
+            code += "s = string_join(s, s); t = string_join(s, \"\");";
+        }
+        code += "print(string_charat(s, 100000) == 'a'); return string_length(t) / 65536; }";
+
+        assert_eq!(run_code(&code, ""), (Ok(4), "true\n".to_string()));
+    }
+}
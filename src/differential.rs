@@ -0,0 +1,235 @@
+//! Differential testing of the native backend against the reference interpreter.
+//!
+//! Every test program is run by both, which have to agree on how the program terminates and on
+//! its output. The first program on which they disagree is shrunk line by line to a smaller
+//! program which still shows the difference.
+
+use std::{
+    fmt,
+    path::Path,
+    process::{Command, Stdio},
+    sync::Mutex,
+};
+
+use crate::{
+    codegen::toolchain::TempDir,
+    test_runner::{self, Expectation, Options, Termination},
+};
+
+/// How a program ended and what it printed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Observation {
+    pub termination: Termination,
+    pub stdout: Vec<u8>,
+}
+
+/// The result of running a program both ways.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Comparison {
+    /// The compiler rejects the program, so there's nothing to compare.
+    Invalid,
+    Agree,
+    Differ {
+        interpreter: Observation,
+        native: Observation,
+    },
+}
+
+/// Compares the interpreter with the native backend on every test program in the directory.
+/// Returns the exit code of the runner, which is `1` if they differ on any program.
+pub fn run(options: &Options) -> i32 {
+    let mut tests = Vec::new();
+    if let Err(error) = test_runner::collect_tests(&options.dir, &mut tests) {
+        eprintln!("couldn't read {}: {}", options.dir.display(), error);
+        return 2;
+    }
+    // programs which shouldn't compile can't be compared
+    tests.retain(|(_, expectation)| *expectation != Expectation::Error);
+    tests.sort_by(|(left, _), (right, _)| left.cmp(right));
+
+    let differing = Mutex::new(Vec::new());
+    test_runner::in_parallel(&tests, |(path, _)| match compare(options, path, path) {
+        Ok(Comparison::Differ {
+            interpreter,
+            native,
+        }) => {
+            eprintln!(
+                "DIFFER {}\n    interpreter: {}\n    native:      {}",
+                path.display(),
+                interpreter,
+                native
+            );
+            differing.lock().unwrap().push(path);
+        }
+        Ok(_) => {}
+        Err(message) => {
+            eprintln!("FAIL {}: {}", path.display(), message);
+            differing.lock().unwrap().push(path);
+        }
+    });
+
+    let mut differing = differing.into_inner().unwrap();
+    differing.sort();
+    println!(
+        "{} programs agree, {} differ",
+        tests.len() - differing.len(),
+        differing.len()
+    );
+
+    let Some(first) = differing.first() else {
+        return 0;
+    };
+    match reproduction(options, first) {
+        Ok(Some(code)) => println!("\nminimised reproduction of {}:\n{}", first.display(), code),
+        Ok(None) => {}
+        Err(message) => eprintln!("couldn't minimise {}: {}", first.display(), message),
+    }
+
+    1
+}
+
+/// Shrinks the program as long as the interpreter and the native backend still differ on it.
+fn reproduction(options: &Options, test: &Path) -> Result<Option<String>, String> {
+    let code = std::fs::read_to_string(test).map_err(|error| error.to_string())?;
+    let dir = TempDir::new().map_err(|error| error.to_string())?;
+    let candidate = dir.path.join("reproduction.c0");
+
+    let lines: Vec<&str> = code.lines().collect();
+    let lines = minimise(lines, |lines| {
+        std::fs::write(&candidate, lines.join("\n")).is_ok()
+            && matches!(
+                compare(options, &candidate, test),
+                Ok(Comparison::Differ { .. })
+            )
+    });
+
+    match lines.is_empty() {
+        true => Ok(None),
+        false => Ok(Some(lines.join("\n"))),
+    }
+}
+
+/// Removes chunks of lines, which halve in size down to single lines, as long as the remaining
+/// lines are still interesting. Returns no lines if the input isn't interesting in the first place.
+pub fn minimise<'a>(
+    mut lines: Vec<&'a str>,
+    mut interesting: impl FnMut(&[&'a str]) -> bool,
+) -> Vec<&'a str> {
+    if !interesting(&lines) {
+        return Vec::new();
+    }
+
+    let mut chunk = lines.len().div_ceil(2);
+    while chunk > 0 {
+        let mut start = 0;
+        while start < lines.len() {
+            let end = lines.len().min(start + chunk);
+            let candidate: Vec<&str> = [&lines[..start], &lines[end..]].concat();
+
+            match interesting(&candidate) {
+                true => lines = candidate,
+                false => start = end,
+            }
+        }
+
+        chunk /= 2;
+    }
+
+    lines
+}
+
+/// Runs the program with the interpreter and compiled, with the input of the test.
+fn compare(options: &Options, program: &Path, test: &Path) -> Result<Comparison, String> {
+    let dir = TempDir::new().map_err(|error| format!("couldn't create a directory: {}", error))?;
+    let executable = dir.path.join("program");
+
+    let mut compiler = Command::new(&options.compiler);
+    compiler.arg(program).arg("-o").arg(&executable);
+    let compiled = observe(compiler, test, &dir.path, options)?;
+    match compiled.termination {
+        Termination::Exited(0) => {}
+        Termination::Exited(42 | 7) => return Ok(Comparison::Invalid),
+        termination => return Err(format!("the compiler {}", termination)),
+    }
+
+    let native = observe(Command::new(&executable), test, &dir.path, options)?;
+    let mut interpreter = Command::new(&options.compiler);
+    interpreter.arg("--interpret").arg(program);
+    let interpreter = observe(interpreter, test, &dir.path, options)?;
+
+    match interpreter == native {
+        true => Ok(Comparison::Agree),
+        false => Ok(Comparison::Differ {
+            interpreter,
+            native,
+        }),
+    }
+}
+
+/// Runs the command with the input of the test and collects its output in `dir`.
+fn observe(
+    mut command: Command,
+    test: &Path,
+    dir: &Path,
+    options: &Options,
+) -> Result<Observation, String> {
+    // a file can't fill up like a pipe while the process is running
+    let stdout_path = dir.join("stdout");
+    let stdout = std::fs::File::create(&stdout_path)
+        .map_err(|error| format!("couldn't create a file: {}", error))?;
+
+    let child = command
+        .stdin(test_runner::input(test))
+        .stdout(stdout)
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|error| format!("couldn't run {:?}: {}", command.get_program(), error))?;
+    let termination = test_runner::wait(child, options.timeout)?;
+
+    let stdout = std::fs::read(&stdout_path)
+        .map_err(|error| format!("couldn't read the output: {}", error))?;
+    Ok(Observation {
+        termination,
+        stdout,
+    })
+}
+
+impl fmt::Display for Observation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} after printing \"{}\"",
+            self.termination,
+            self.stdout.escape_ascii()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minimise_to_relevant_lines() {
+        let lines = vec!["a", "b", "c", "d", "e", "f", "g"];
+        let minimised = minimise(lines, |lines| lines.contains(&"b") && lines.contains(&"f"));
+
+        assert_eq!(minimised, vec!["b", "f"]);
+    }
+
+    #[test]
+    fn minimise_keeps_order() {
+        let lines = vec!["x", "1", "2", "y", "3"];
+        let minimised = minimise(lines, |lines| {
+            let position = |line| lines.iter().position(|l| *l == line);
+            matches!((position("x"), position("y")), (Some(x), Some(y)) if x < y)
+        });
+
+        assert_eq!(minimised, vec!["x", "y"]);
+    }
+
+    #[test]
+    fn minimise_uninteresting() {
+        assert!(minimise(vec!["a"], |_| false).is_empty());
+    }
+}
@@ -0,0 +1,301 @@
+//! Runs the stages of the compiler which the command line asks for and decides the exit code.
+//!
+//! The exit codes are part of the interface of the compiler since test harnesses rely on them:
+//!
+//! | exit code | meaning                                           |
+//! |-----------|---------------------------------------------------|
+//! | 0         | the requested stage has been written              |
+//! | 1         | the input couldn't be read or the output written  |
+//! | 2         | the command line arguments don't make sense       |
+//! | 3         | the assembly couldn't be turned into an object    |
+//! | 4         | the program couldn't be linked                    |
+//! | 7         | the program is semantically invalid               |
+//! | 42        | the program couldn't be lexed or parsed           |
+//!
+//! With `--interpret` the compiler exits like the compiled program would instead.
+
+use std::{
+    fmt,
+    io::{Read, Write},
+};
+
+use chumsky::error::Rich;
+
+use crate::{
+    cli::{Cli, Emit, Input, Output},
+    codegen, interpreter, lexer,
+    passes::{self, PassManager},
+};
+
+#[derive(Debug)]
+pub enum CompileOutcome {
+    /// The requested stage has been written.
+    Emitted,
+    /// The program ran in the interpreter, which either returned from `main` or raised an
+    /// exception.
+    Interpreted(Result<i32, interpreter::Exception>),
+}
+
+#[derive(Debug)]
+pub enum CompileError {
+    Usage(String),
+    Io {
+        /// What has been done when the error occurred.
+        context: String,
+        error: std::io::Error,
+    },
+    /// The messages of the errors, prefixed with their line.
+    Lexing(Vec<String>),
+    /// Like [CompileError::Lexing].
+    Parsing(Vec<String>),
+    Semantic {
+        /// The name of the check which failed.
+        check: &'static str,
+    },
+    /// Assembling the generated code failed.
+    Codegen(String),
+    Linker(String),
+}
+
+impl CompileError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CompileError::Io { .. } => 1,
+            CompileError::Usage(_) => 2,
+            CompileError::Codegen(_) => 3,
+            CompileError::Linker(_) => 4,
+            CompileError::Semantic { .. } => 7,
+            CompileError::Lexing(_) | CompileError::Parsing(_) => 42,
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CompileError::Usage(message) => write!(f, "error: {}", message),
+            CompileError::Io { context, error } => write!(f, "couldn't {}: {}", context, error),
+            CompileError::Lexing(errors) | CompileError::Parsing(errors) => {
+                write!(f, "{}", errors.join("\n"))
+            }
+            CompileError::Semantic {
+                check: "unsupported_features",
+            } => write!(
+                f,
+                "`\\length` and `loop_invariant` aren't supported, since there are no arrays or loops"
+            ),
+            CompileError::Semantic { check } => {
+                write!(f, "the program doesn't pass the `{}` check", check)
+            }
+            CompileError::Codegen(message) | CompileError::Linker(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+pub fn compile(cli: &Cli) -> Result<CompileOutcome, CompileError> {
+    let options = interpreter::Options {
+        dynamic_checks: cli.dynamic_checks,
+        // keeps every string alive, only useful for debugging
+        garbage_collection: cli.garbage_collection,
+    };
+    let pass_options = passes::Options {
+        dynamic_checks: cli.dynamic_checks,
+        time_passes: cli.time_passes,
+        dump_after: cli.dump_after.clone(),
+    };
+
+    // the interpreter runs on the AST
+    let passes = match cli.interpret {
+        true => PassManager::front_end(pass_options),
+        false => PassManager::compiler(pass_options),
+    };
+    if let Some(pass) = cli
+        .dump_after
+        .as_deref()
+        .filter(|pass| !passes.contains(pass))
+    {
+        return Err(CompileError::Usage(format!(
+            "there's no pass named `{}`",
+            pass
+        )));
+    }
+
+    let input_file = read_input(&cli.input).map_err(|error| CompileError::Io {
+        context: format!("read {}", cli.input),
+        error,
+    })?;
+
+    if cli.emit == Emit::Tokens {
+        let (tokens, errors) = lexer::lex(&input_file).into_output_errors();
+        let tokens = match tokens {
+            Some(tokens) if errors.is_empty() => tokens,
+            _ => return Err(CompileError::Lexing(messages(&input_file, errors))),
+        };
+
+        let tokens: String = tokens
+            .iter()
+            .map(|(token, span)| format!("{} {:?}\n", span, token))
+            .collect();
+        return write_output(&cli.output, tokens.as_bytes());
+    }
+
+    let program = crate::parse(&input_file)?;
+    if cli.emit == Emit::Ast {
+        return write_output(&cli.output, format!("{:#?}\n", program).as_bytes());
+    }
+
+    let ir = passes
+        .run(&program)
+        .map_err(|check| CompileError::Semantic { check })?;
+
+    if cli.interpret {
+        return Ok(CompileOutcome::Interpreted(interpreter::interpret(
+            &program, options,
+        )));
+    }
+
+    let ir = ir.expect("the compiler lowers the program");
+    if cli.emit == Emit::Ir {
+        return write_output(&cli.output, ir.to_string().as_bytes());
+    }
+
+    let asm = codegen::asm(&ir);
+    match (cli.emit, &cli.output) {
+        (Emit::Obj, Output::File(path)) => {
+            codegen::toolchain::assemble(&asm, path).map_err(CompileError::Codegen)?
+        }
+        (Emit::Exe, Output::File(path)) => {
+            codegen::toolchain::link(&asm, path, cli.garbage_collection)
+                .map_err(CompileError::Linker)?
+        }
+        _ => return write_output(&cli.output, asm.as_bytes()),
+    }
+
+    Ok(CompileOutcome::Emitted)
+}
+
+fn read_input(input: &Input) -> std::io::Result<String> {
+    match input {
+        Input::Stdin => {
+            let mut code = String::new();
+            std::io::stdin().read_to_string(&mut code)?;
+            Ok(code)
+        }
+        Input::File(path) => std::fs::read_to_string(path),
+    }
+}
+
+/// Prefixes the errors with their line.
+pub(crate) fn messages<T: fmt::Display>(code: &str, errors: Vec<Rich<'_, T>>) -> Vec<String> {
+    errors
+        .into_iter()
+        .map(|error| {
+            let line = code[..error.span().start].matches('\n').count() + 1;
+            format!("line {}: {}", line, error)
+        })
+        .collect()
+}
+
+/// Writes a textual stage.
+fn write_output(output: &Output, bytes: &[u8]) -> Result<CompileOutcome, CompileError> {
+    let result = match output {
+        Output::Stdout => std::io::stdout().write_all(bytes),
+        Output::File(path) => std::fs::write(path, bytes),
+    };
+
+    result
+        .map(|_| CompileOutcome::Emitted)
+        .map_err(|error| CompileError::Io {
+            context: "write the output".to_string(),
+            error,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codegen::toolchain::TempDir;
+
+    fn compile_code(code: &str, emit: Emit) -> Result<CompileOutcome, CompileError> {
+        let dir = TempDir::new().unwrap();
+        let input = dir.path.join("main.c0");
+        std::fs::write(&input, code).unwrap();
+
+        compile(&Cli {
+            input: Input::File(input),
+            output: Output::File(dir.path.join("main")),
+            emit,
+            interpret: false,
+            dynamic_checks: false,
+            garbage_collection: true,
+            time_passes: false,
+            dump_after: None,
+        })
+    }
+
+    fn exit_code(code: &str) -> i32 {
+        match compile_code(code, Emit::Ir) {
+            Ok(_) => 0,
+            Err(error) => error.exit_code(),
+        }
+    }
+
+    #[test]
+    fn exit_codes() {
+        assert_eq!(exit_code("int main() { return 0; }"), 0);
+        assert_eq!(exit_code("int main() { return 0 $ 1; }"), 42);
+        assert_eq!(exit_code("int main() { return 0 1; }"), 42);
+        assert_eq!(exit_code("int main() { return x; }"), 7);
+    }
+
+    #[test]
+    fn unsupported_features() {
+        let error = compile_code(
+            "int main() { //@loop_invariant true;\n return 0; }",
+            Emit::Ir,
+        )
+        .unwrap_err();
+
+        assert_eq!(error.exit_code(), 7);
+        assert!(error.to_string().contains("aren't supported"));
+    }
+
+    #[test]
+    fn missing_input() {
+        let error = compile(&Cli {
+            input: Input::File("/does/not/exist.c0".into()),
+            output: Output::Stdout,
+            emit: Emit::Ir,
+            interpret: false,
+            dynamic_checks: false,
+            garbage_collection: true,
+            time_passes: false,
+            dump_after: None,
+        })
+        .unwrap_err();
+
+        assert_eq!(error.exit_code(), 1);
+    }
+
+    #[test]
+    fn lexing_and_parsing() {
+        assert!(matches!(
+            compile_code("int main() { return 0 $ 1; }", Emit::Ir),
+            Err(CompileError::Lexing(_))
+        ));
+        assert!(matches!(
+            compile_code("int main() { return 0 1; }", Emit::Ir),
+            Err(CompileError::Parsing(_))
+        ));
+    }
+
+    #[test]
+    fn executable() {
+        assert!(matches!(
+            compile_code("int main() { return 0; }", Emit::Exe),
+            Ok(CompileOutcome::Emitted)
+        ));
+    }
+}
@@ -0,0 +1,382 @@
+//! Formats C0 code in the canonical style of the pretty-printer and keeps its comments.
+//!
+//! The code is parsed and printed again, so every statement gets a line of its own with four
+//! spaces of indentation, binary and assignment operators are surrounded by spaces and only the
+//! parentheses which are needed are kept. Comments belong to the function header, statement or
+//! closing `}` they are written in or in front of: a comment which follows one of them on the
+//! same line stays there, every other comment gets a line of its own in front of it. Blank lines
+//! between statements and comments are kept, but at most one in a row.
+
+use std::io::{Read, Write};
+
+use chumsky::span::SimpleSpan;
+
+use crate::{
+    CompileError,
+    cli::Input,
+    grammar::{Function, Program, Statement, print::Header},
+    lexer::{self, Spanned, Token},
+};
+
+/// Formats every input, files are overwritten and stdin is written to stdout. With `check`
+/// nothing is written and the inputs which aren't formatted are listed instead.
+/// Returns the exit code, which is `1` if an input isn't formatted or the code of the last error.
+pub fn run(inputs: &[Input], check: bool) -> i32 {
+    let mut exit_code = 0;
+
+    for input in inputs {
+        match format_input(input, check) {
+            Ok(true) => {}
+            Ok(false) => {
+                println!("{} isn't formatted", input);
+                if exit_code == 0 {
+                    exit_code = 1;
+                }
+            }
+            Err(error) => {
+                eprintln!("{}: {}", input, error);
+                exit_code = error.exit_code();
+            }
+        }
+    }
+
+    exit_code
+}
+
+/// Returns `false` if the input isn't formatted and should only be checked.
+fn format_input(input: &Input, check: bool) -> Result<bool, CompileError> {
+    let mut code = String::new();
+    let read = match input {
+        Input::Stdin => std::io::stdin().read_to_string(&mut code).map(|_| ()),
+        Input::File(path) => std::fs::read_to_string(path).map(|file| code = file),
+    };
+    read.map_err(|error| CompileError::Io {
+        context: format!("read {}", input),
+        error,
+    })?;
+
+    let formatted = format(&code)?;
+    if check {
+        return Ok(formatted == code);
+    }
+
+    let written = match input {
+        Input::Stdin => std::io::stdout().write_all(formatted.as_bytes()),
+        Input::File(_) if formatted == code => Ok(()),
+        Input::File(path) => std::fs::write(path, formatted),
+    };
+    written.map_err(|error| CompileError::Io {
+        context: format!("write {}", input),
+        error,
+    })?;
+
+    Ok(true)
+}
+
+/// Formats the code, which has to be lexed and parsed without errors.
+pub fn format(code: &str) -> Result<String, CompileError> {
+    let tokens = crate::lex(code)?;
+    let comments = lexer::comments(code, &tokens);
+    let program = crate::parse_tokens(code, tokens.clone())?;
+
+    let mut formatter = Formatter {
+        code,
+        token_ends: tokens
+            .iter()
+            .map(|(_, span)| token_end(code, *span))
+            .collect(),
+        output: String::new(),
+        position: 0,
+        block_start: false,
+    };
+    formatter.format(&lines(code, &program, &tokens), comments);
+
+    Ok(formatter.output)
+}
+
+/// What gets printed on a line of its own.
+#[derive(Debug)]
+enum Item<'a, 'src> {
+    Header(&'a Function<'src>),
+    Statement(&'a Statement<'src>),
+    /// The `}` at the end of a function.
+    End,
+}
+
+#[derive(Debug)]
+struct Line<'a, 'src> {
+    item: Item<'a, 'src>,
+    /// Where the item starts and ends in the code.
+    start: usize,
+    end: usize,
+}
+
+/// Finds where the functions and statements of the program are in its tokens.
+fn lines<'a, 'src>(
+    code: &str,
+    program: &'a Program<'src>,
+    tokens: &[Spanned<Token<'src>>],
+) -> Vec<Line<'a, 'src>> {
+    let find = |from: usize, token: Token<'_>| {
+        let offset = tokens[from..].iter().position(|(t, _)| *t == token);
+        from + offset.expect("the code has been parsed")
+    };
+    let line = |item, first: usize, last: usize| Line {
+        item,
+        start: tokens[first].1.start,
+        end: token_end(code, tokens[last].1),
+    };
+
+    let mut lines = Vec::new();
+    let mut next = 0;
+    for function in &program.0 {
+        // neither the parameters nor the contracts contain a `{`
+        let brace = find(next, Token::Ctrl('{'));
+        lines.push(line(Item::Header(function), next, brace));
+        next = brace + 1;
+
+        for statement in &function.statements {
+            let last = match statement {
+                // adjacent annotations make up a single statement
+                Statement::Annotations(_) => {
+                    let mut last = find(next, Token::AnnotationEnd);
+                    while tokens
+                        .get(last + 1)
+                        .is_some_and(|(t, _)| *t == Token::AnnotationStart)
+                    {
+                        last = find(last + 1, Token::AnnotationEnd);
+                    }
+                    last
+                }
+                _ => find(next, Token::Ctrl(';')),
+            };
+            lines.push(line(Item::Statement(statement), next, last));
+            next = last + 1;
+        }
+
+        lines.push(line(Item::End, next, next));
+        next += 1;
+    }
+
+    lines
+}
+
+/// Where the token ends, the newline which ends a `//@` annotation belongs to the following line.
+fn token_end(code: &str, span: SimpleSpan) -> usize {
+    match &code[span.into_range()] {
+        "\n" => span.start,
+        _ => span.end,
+    }
+}
+
+struct Formatter<'src> {
+    code: &'src str,
+    /// Where every token ends in the code, in order.
+    token_ends: Vec<usize>,
+    output: String,
+    /// Where the last item or comment which has been written ends in the code.
+    position: usize,
+    /// Nothing has been written into the body of the current function yet.
+    block_start: bool,
+}
+
+impl Formatter<'_> {
+    fn format(&mut self, lines: &[Line<'_, '_>], comments: Vec<Spanned<&str>>) {
+        let mut comments = comments.into_iter().peekable();
+
+        for line in lines {
+            let indent = match line.item {
+                Item::Statement(_) => "    ",
+                Item::Header(_) | Item::End => "",
+            };
+            // comments in front of the `}` are still in the body
+            let comment_indent = match line.item {
+                Item::Statement(_) | Item::End => "    ",
+                Item::Header(_) => "",
+            };
+            // functions are always separated by a blank line
+            let mut separate = matches!(line.item, Item::Header(_)) && !self.output.is_empty();
+
+            while let Some((comment, span)) = comments.next_if(|(_, span)| span.start < line.end) {
+                self.start_line(span.start, separate);
+                self.output.push_str(comment_indent);
+                self.output.push_str(comment);
+                self.output.push('\n');
+                self.position = span.end;
+                separate = false;
+            }
+
+            if !matches!(line.item, Item::End) {
+                self.start_line(line.start, separate);
+            }
+            self.output.push_str(indent);
+            let item = match line.item {
+                Item::Header(function) => Header(function).to_string(),
+                Item::Statement(statement) => statement.to_string(),
+                Item::End => "}".to_string(),
+            };
+            self.output.push_str(&item);
+            self.position = line.end;
+            self.block_start = matches!(line.item, Item::Header(_));
+
+            let code = self.code;
+            while let Some((comment, span)) =
+                comments.next_if(|(_, span)| !code[self.position..span.start].contains('\n'))
+            {
+                self.output.push(' ');
+                self.output.push_str(comment);
+                self.position = span.end;
+            }
+            self.output.push('\n');
+        }
+
+        // the comments at the end of the code
+        for (comment, span) in comments {
+            self.start_line(span.start, false);
+            self.output.push_str(comment);
+            self.output.push('\n');
+            self.position = span.end;
+        }
+    }
+
+    /// Writes a blank line if it's forced or if there's one in the code between `start` and the
+    /// token or comment in front of it, unless the line is the first one of the code or of a
+    /// function body.
+    fn start_line(&mut self, start: usize, forced: bool) {
+        let tokens_before = self.token_ends.partition_point(|&end| end <= start);
+        let previous = match tokens_before {
+            0 => 0,
+            count => self.token_ends[count - 1],
+        };
+        // comments within an item are written in front of it
+        let gap = &self.code[previous.max(self.position).min(start)..start];
+        let blank_in_code = gap.matches('\n').count() >= 2;
+
+        if (forced || blank_in_code)
+            && !self.block_start
+            && !self.output.is_empty()
+            && !self.output.ends_with("\n\n")
+        {
+            self.output.push('\n');
+        }
+        self.block_start = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{self, Rng};
+
+    fn formatted(code: &str) -> String {
+        let formatted = format(code).unwrap();
+        assert_eq!(format(&formatted).unwrap(), formatted, "not idempotent");
+
+        formatted
+    }
+
+    #[test]
+    fn canonical_style() {
+        assert_eq!(
+            formatted(
+                "int f(int a,bool b)//@requires b;\n{return(a+1)*2;}\nint main(){int x=f(1,true);\
+                 x+=-x%(3);print( x );  return x ; }"
+            ),
+            "int f(int a, bool b) /*@ requires b; @*/ {
+    return (a + 1) * 2;
+}
+
+int main() {
+    int x = f(1, true);
+    x += -x % 3;
+    print(x);
+    return x;
+}
+"
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let code = "// the entry point
+/* returns
+   zero */
+int main() { // starts here
+
+  int x = 1; /* one */ // really
+
+
+  /* nested /* block */ comment */
+  x /* moved */ += 2;
+  //@assert x == 3;
+  // before the end
+} // main
+// the end
+";
+
+        assert_eq!(
+            formatted(code),
+            "// the entry point
+/* returns
+   zero */
+int main() { // starts here
+    int x = 1; /* one */ // really
+
+    /* nested /* block */ comment */
+    /* moved */
+    x += 2;
+    /*@ assert x == 3; @*/
+    // before the end
+} // main
+// the end
+"
+        );
+    }
+
+    #[test]
+    fn comment_within_a_statement() {
+        assert_eq!(
+            formatted(
+                "int main() {\n    int x = 0;\n    int y = 1 +\n    /* mid */ 2;\n\n    return y;\n}"
+            ),
+            "int main() {\n    int x = 0;\n    /* mid */\n    int y = 1 + 2;\n\n    return y;\n}\n"
+        );
+    }
+
+    #[test]
+    fn nested_negation() {
+        assert_eq!(
+            formatted("int main() { return - -1 - (-(-x)); }"),
+            "int main() {\n    return - -1 - - -x;\n}\n"
+        );
+    }
+
+    #[test]
+    fn blank_lines_between_functions() {
+        assert_eq!(
+            formatted("void f() {}\n// g\nvoid g() {}\n\n\n// main\n\nint main() { return 0; }"),
+            "void f() {\n}\n\n// g\nvoid g() {\n}\n\n// main\n\nint main() {\n    return 0;\n}\n"
+        );
+    }
+
+    #[test]
+    fn generated_programs_are_formatted() {
+        for seed in 0..20 {
+            let code = generator::generate(&mut Rng::new(seed)).to_string();
+
+            assert_eq!(format(&code).unwrap(), code);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            format("int main() { return 1 $ 2; }"),
+            Err(CompileError::Lexing(_))
+        ));
+        assert!(matches!(
+            format("int main() { return; "),
+            Err(CompileError::Parsing(_))
+        ));
+    }
+}
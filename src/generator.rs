@@ -0,0 +1,619 @@
+//! Generates random programs which pass the analyzers.
+//!
+//! The programs are meant to stress the parser, the analyzers and the backends: they declare and
+//! assign variables with every assignment operator, nest every operator in expressions and call
+//! the builtins and each other. Functions only call the functions defined before them, so every
+//! program terminates, but it might still divide by zero or make a builtin abort. Expressions and
+//! lvalues are also wrapped in redundant parentheses, which the printer drops, so printing and
+//! parsing a program gives back the same AST only
+//! [without parentheses](Program::without_parentheses).
+//!
+//! The decisions come from [Choices], which are either a seeded [Rng] for property tests or the
+//! [Bytes] of a fuzzer. Choice `0` is always the simplest option, so a fuzzer which runs out of
+//! bytes gets a small program.
+
+use crate::{grammar::*, library::Builtin};
+
+/// The source of every decision of the generator.
+pub trait Choices {
+    /// Returns a number in `0..bound`, where `bound` isn't `0`.
+    fn below(&mut self, bound: usize) -> usize;
+}
+
+/// A deterministic pseudo random number generator (SplitMix64).
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+}
+
+impl Choices for Rng {
+    fn below(&mut self, bound: usize) -> usize {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+
+        (z % bound as u64) as usize
+    }
+}
+
+/// Takes the choices from the input of a fuzzer, one byte per choice.
+#[derive(Debug, Clone)]
+pub struct Bytes<'a>(pub &'a [u8]);
+
+impl Choices for Bytes<'_> {
+    fn below(&mut self, bound: usize) -> usize {
+        match self.0.split_first() {
+            Some((byte, rest)) => {
+                self.0 = rest;
+                *byte as usize % bound
+            }
+            None => 0,
+        }
+    }
+}
+
+const FUNCTIONS: [&str; 6] = ["f0", "f1", "f2", "f3", "f4", "f5"];
+// more than six parameters are passed on the stack
+const PARAMS: [&str; 8] = ["p0", "p1", "p2", "p3", "p4", "p5", "p6", "p7"];
+const VARIABLES: [&str; 16] = [
+    "v0", "v1", "v2", "v3", "v4", "v5", "v6", "v7", "v8", "v9", "v10", "v11", "v12", "v13", "v14",
+    "v15",
+];
+
+const DECNUMS: &[&str] = &[
+    "0",
+    "1",
+    "2",
+    "3",
+    "7",
+    "10",
+    "31",
+    "32",
+    "255",
+    "65536",
+    "2147483647",
+    "2147483648",
+];
+const HEXNUMS: &[&str] = &[
+    "0", "1", "ff", "FF", "7fffffff", "80000000", "ffffffff", "DeadBeef",
+];
+/// Divisors which can't trap, so not every division ends the program.
+const DIVISORS: &[&str] = &["1", "2", "3", "7", "10", "255", "65536"];
+const INDICES: &[&str] = &["0", "1", "2", "3"];
+/// Arguments of `char_chr` which don't abort.
+const ORDS: &[&str] = &["65", "0", "10", "32", "48", "97", "126", "127"];
+const CHARS: &[&str] = &["a", "Z", "0", " ", "\"", "\\n", "\\t", "\\'", "\\\\", "\\0"];
+const STRINGS: &[&str] = &[
+    "",
+    "a",
+    "hello world",
+    "'",
+    "\\n",
+    "tab\\there",
+    "\\\"quoted\\\"",
+    "back\\\\slash",
+];
+
+const VALUE_TYPES: [Type; 4] = [Type::Int, Type::Bool, Type::Char, Type::String];
+const ARITHMETIC: [BinOperation; 5] = [
+    BinOperation::Plus,
+    BinOperation::Minus,
+    BinOperation::Multiplication,
+    BinOperation::Division,
+    BinOperation::Mod,
+];
+const ASNOPS: [AsNop; 6] = [
+    AsNop::Equal,
+    AsNop::PlusEqual,
+    AsNop::MinusEqual,
+    AsNop::MultEqual,
+    AsNop::DivEqual,
+    AsNop::ModEqual,
+];
+
+const MAX_STATEMENTS: usize = 16;
+const MAX_DEPTH: usize = 4;
+/// The calls of other functions within a function, which bounds the calls at runtime.
+const MAX_CALLS: usize = 2;
+
+/// Generates a program which passes the analyzers.
+pub fn generate(choices: &mut impl Choices) -> Program<'static> {
+    program(choices, false).0
+}
+
+/// Generates a program like [generate], but compound assignments may also read variables which
+/// haven't been initialised. Returns whether one does, as the analyzers have to reject the program
+/// then.
+pub fn generate_with_uninitialised_reads(choices: &mut impl Choices) -> (Program<'static>, bool) {
+    program(choices, true)
+}
+
+fn program(choices: &mut impl Choices, uninitialised_reads: bool) -> (Program<'static>, bool) {
+    let mut generator = Generator {
+        choices,
+        functions: Vec::new(),
+        initialised: Vec::new(),
+        uninitialised: Vec::new(),
+        next_variable: 0,
+        calls: 0,
+        uninitialised_reads,
+        reads_uninitialised: false,
+    };
+
+    let mut functions = Vec::new();
+    for ident in &FUNCTIONS[..generator.choose(FUNCTIONS.len() + 1)] {
+        let return_type =
+            [Type::Int, Type::Bool, Type::Char, Type::String, Type::Void][generator.choose(5)];
+        let params = PARAMS[..generator.choose(PARAMS.len() + 1)]
+            .iter()
+            .map(|ident| Param {
+                ty: generator.value_type(),
+                ident: Identifier(ident),
+            })
+            .collect();
+
+        functions.push(generator.function(ident, return_type, params));
+    }
+    functions.push(generator.function("main", Type::Int, Vec::new()));
+
+    (Program(functions), generator.reads_uninitialised)
+}
+
+#[derive(Debug)]
+struct Signature {
+    ident: &'static str,
+    params: Vec<Type>,
+    return_type: Type,
+}
+
+struct Generator<'c, C> {
+    choices: &'c mut C,
+    /// The functions which have been generated so far and may be called.
+    functions: Vec<Signature>,
+    /// The variables which may be read.
+    initialised: Vec<(&'static str, Type)>,
+    /// The variables which may only be assigned, as assignments don't count as initialisation.
+    uninitialised: Vec<(&'static str, Type)>,
+    next_variable: usize,
+    /// How many more functions may be called in the current function.
+    calls: usize,
+    /// Compound assignments may read the uninitialised variables.
+    uninitialised_reads: bool,
+    /// A compound assignment has read an uninitialised variable.
+    reads_uninitialised: bool,
+}
+
+impl<C: Choices> Generator<'_, C> {
+    fn choose(&mut self, bound: usize) -> usize {
+        self.choices.below(bound)
+    }
+
+    fn value_type(&mut self) -> Type {
+        VALUE_TYPES[self.choose(VALUE_TYPES.len())]
+    }
+
+    fn function(
+        &mut self,
+        ident: &'static str,
+        return_type: Type,
+        params: Vec<Param<'static>>,
+    ) -> Function<'static> {
+        self.initialised = params
+            .iter()
+            .map(|param| (param.ident.0, param.ty))
+            .collect();
+        self.uninitialised.clear();
+        self.next_variable = 0;
+        self.calls = MAX_CALLS;
+
+        let mut statements = Vec::new();
+        while statements.len() < MAX_STATEMENTS && self.choose(8) != 0 {
+            statements.push(self.statement(return_type));
+        }
+        match return_type {
+            Type::Void if self.choose(2) == 0 => {}
+            Type::Void => statements.push(Statement::Return(None)),
+            ty => statements.push(Statement::Return(Some(self.exp(ty, MAX_DEPTH)))),
+        }
+
+        // the function can only be called by the following functions, so there's no recursion
+        self.functions.push(Signature {
+            ident,
+            params: params.iter().map(|param| param.ty).collect(),
+            return_type,
+        });
+
+        Function {
+            return_type,
+            ident: Identifier(ident),
+            params,
+            annotations: Vec::new(),
+            statements,
+        }
+    }
+
+    fn statement(&mut self, return_type: Type) -> Statement<'static> {
+        match self.choose(16) {
+            0..=2 => self.print(),
+            3..=6 => self.declaration(),
+            7..=11 => self.assignment(),
+            12 | 13 => match self.call(None, MAX_DEPTH - 1) {
+                Some(call) => Statement::Exp(call),
+                None => self.print(),
+            },
+            14 => {
+                // comparing an expression with itself keeps the assertion from failing
+                let ty = [Type::Int, Type::Bool, Type::Char][self.choose(3)];
+                let exp = self.exp(ty, MAX_DEPTH - 1);
+
+                Statement::Assert(binop(exp.clone(), BinOperation::Equal, exp))
+            }
+            _ => match return_type {
+                Type::Void => Statement::Return(None),
+                ty => Statement::Return(Some(self.exp(ty, MAX_DEPTH))),
+            },
+        }
+    }
+
+    fn print(&mut self) -> Statement<'static> {
+        let ty = [Type::Int, Type::Bool, Type::String][self.choose(3)];
+
+        Statement::Print(self.exp(ty, MAX_DEPTH))
+    }
+
+    fn declaration(&mut self) -> Statement<'static> {
+        let Some(ident) = VARIABLES.get(self.next_variable) else {
+            return self.print();
+        };
+        self.next_variable += 1;
+
+        let ty = self.value_type();
+        let decl = match self.choose(4) {
+            0 => {
+                self.uninitialised.push((ident, ty));
+                Declaration::Ident {
+                    ty,
+                    ident: Identifier(ident),
+                }
+            }
+            _ => {
+                // the variable can't be used in its own initialiser
+                let exp = self.exp(ty, MAX_DEPTH);
+                self.initialised.push((ident, ty));
+                Declaration::IdentExp {
+                    ty,
+                    ident: Identifier(ident),
+                    exp,
+                }
+            }
+        };
+
+        Statement::Decl(decl)
+    }
+
+    fn assignment(&mut self) -> Statement<'static> {
+        let count = self.initialised.len() + self.uninitialised.len();
+        if count == 0 {
+            return self.declaration();
+        }
+
+        let index = self.choose(count);
+        let (ident, ty, initialised) = match self.initialised.get(index) {
+            Some(&(ident, ty)) => (ident, ty, true),
+            None => {
+                let (ident, ty) = self.uninitialised[index - self.initialised.len()];
+                (ident, ty, false)
+            }
+        };
+
+        let mut asnop = match ty {
+            Type::Int => ASNOPS[self.choose(ASNOPS.len())],
+            _ => AsNop::Equal,
+        };
+        if asnop != AsNop::Equal && !initialised {
+            match self.uninitialised_reads {
+                true => self.reads_uninitialised = true,
+                false => asnop = AsNop::Equal,
+            }
+        }
+        let exp = match asnop {
+            AsNop::DivEqual | AsNop::ModEqual if self.choose(4) != 0 => self.literal(DIVISORS),
+            _ => self.exp(ty, MAX_DEPTH),
+        };
+
+        let mut lvalue = LValue::Ident(Identifier(ident));
+        while self.choose(4) == 3 {
+            lvalue = LValue::LValue(Box::new(lvalue));
+        }
+
+        Statement::Simp(SimpleInstruction { lvalue, asnop, exp })
+    }
+
+    fn exp(&mut self, ty: Type, depth: usize) -> Expression<'static> {
+        if depth == 0 {
+            return self.leaf(ty);
+        }
+        let depth = depth - 1;
+
+        let exp = match (ty, self.choose(8)) {
+            (_, 0) => None,
+            (_, 7) => Some(Expression::NestedExp(Box::new(self.exp(ty, depth)))),
+            (Type::Int, 1..=4) => {
+                let op = ARITHMETIC[self.choose(ARITHMETIC.len())];
+                let left = self.exp(Type::Int, depth);
+                let right = match op {
+                    BinOperation::Division | BinOperation::Mod if self.choose(4) != 0 => {
+                        self.literal(DIVISORS)
+                    }
+                    _ => self.exp(Type::Int, depth),
+                };
+
+                Some(binop(left, op, right))
+            }
+            (Type::Int, 5) => {
+                let right = nested(self.exp(Type::Int, depth), |exp| {
+                    matches!(exp, Expression::Binop { .. })
+                });
+
+                Some(Expression::Unop {
+                    op: UnOperation::Minus,
+                    right: Box::new(right),
+                })
+            }
+            (Type::Bool, 1..=4) => {
+                let ty = [Type::Int, Type::Bool, Type::Char][self.choose(3)];
+                let op = [BinOperation::Equal, BinOperation::NotEqual][self.choose(2)];
+                let left = self.exp(ty, depth);
+                let right = self.exp(ty, depth);
+
+                Some(binop(left, op, right))
+            }
+            _ => self.call(Some(ty), depth),
+        };
+
+        exp.unwrap_or_else(|| self.leaf(ty))
+    }
+
+    /// A variable or a literal.
+    fn leaf(&mut self, ty: Type) -> Expression<'static> {
+        let variables: Vec<&'static str> = self
+            .initialised
+            .iter()
+            .filter(|(_, variable_type)| *variable_type == ty)
+            .map(|(ident, _)| *ident)
+            .collect();
+        if !variables.is_empty() && self.choose(2) == 1 {
+            return Expression::Ident(Identifier(variables[self.choose(variables.len())]));
+        }
+
+        match ty {
+            Type::Int if self.choose(2) == 0 => self.literal(DECNUMS),
+            Type::Int => Expression::Intconst(Intconst::Hexnum(Hexnum(
+                HEXNUMS[self.choose(HEXNUMS.len())],
+            ))),
+            Type::Bool => Expression::Boolconst(self.choose(2) == 1),
+            Type::Char => Expression::Charconst(Charconst(CHARS[self.choose(CHARS.len())])),
+            Type::String => {
+                Expression::Stringconst(Stringconst(STRINGS[self.choose(STRINGS.len())]))
+            }
+            Type::Void => unreachable!("there are no values of type void"),
+        }
+    }
+
+    fn literal(&mut self, decnums: &[&'static str]) -> Expression<'static> {
+        Expression::Intconst(Intconst::Decnum(Decnum(
+            decnums[self.choose(decnums.len())],
+        )))
+    }
+
+    /// Calls a builtin or a previous function which returns `ty`, any type if there's none.
+    fn call(&mut self, ty: Option<Type>, depth: usize) -> Option<Expression<'static>> {
+        let returns = |return_type: Type| ty.is_none_or(|ty| ty == return_type);
+        let builtins: Vec<Builtin> = Builtin::ALL
+            .iter()
+            .copied()
+            .filter(|builtin| returns(builtin.signature().1))
+            .collect();
+        let functions: Vec<usize> = match self.calls {
+            0 => Vec::new(),
+            _ => (0..self.functions.len())
+                .filter(|&index| returns(self.functions[index].return_type))
+                .collect(),
+        };
+
+        let count = builtins.len() + functions.len();
+        if count == 0 {
+            return None;
+        }
+        let index = self.choose(count);
+
+        let (ident, args) = match builtins.get(index) {
+            Some(&builtin) => (builtin.name(), self.builtin_args(builtin, depth)),
+            None => {
+                self.calls -= 1;
+                let function = &self.functions[functions[index - builtins.len()]];
+                let (ident, params) = (function.ident, function.params.clone());
+
+                let args = params.iter().map(|ty| self.exp(*ty, depth)).collect();
+                (ident, args)
+            }
+        };
+
+        Some(Expression::Call {
+            ident: Identifier(ident),
+            args,
+        })
+    }
+
+    /// The arguments mostly keep the builtin from aborting, indices are small for example.
+    fn builtin_args(&mut self, builtin: Builtin, depth: usize) -> Vec<Expression<'static>> {
+        let mut args: Vec<Expression<'static>> = builtin
+            .signature()
+            .0
+            .iter()
+            .map(|ty| self.exp(*ty, depth))
+            .collect();
+
+        match builtin {
+            Builtin::StringCharat if self.choose(4) != 0 => args[1] = self.literal(INDICES),
+            Builtin::StringSub if self.choose(4) != 0 => {
+                args[1] = self.literal(INDICES);
+                args[2] = self.literal(INDICES);
+            }
+            Builtin::CharChr if self.choose(4) != 0 => args[0] = self.literal(ORDS),
+            _ => {}
+        }
+
+        args
+    }
+}
+
+/// Builds the operation with the parentheses its operands need, as the operators associate left.
+fn binop(
+    left: Expression<'static>,
+    op: BinOperation,
+    right: Expression<'static>,
+) -> Expression<'static> {
+    let binds_weaker = |exp: &Expression<'_>, strict: bool| match exp {
+        Expression::Binop { op: inner, .. } if strict => inner.precedence() < op.precedence(),
+        Expression::Binop { op: inner, .. } => inner.precedence() <= op.precedence(),
+        _ => false,
+    };
+
+    Expression::Binop {
+        left: Box::new(nested(left, |exp| binds_weaker(exp, true))),
+        op,
+        right: Box::new(nested(right, |exp| binds_weaker(exp, false))),
+    }
+}
+
+fn nested(
+    exp: Expression<'static>,
+    needs_parens: impl Fn(&Expression<'_>) -> bool,
+) -> Expression<'static> {
+    match needs_parens(&exp) {
+        true => Expression::NestedExp(Box::new(exp)),
+        false => exp,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+    use crate::{
+        codegen::toolchain::{self, TempDir},
+        interpreter::{self, Exception},
+    };
+
+    #[test]
+    fn well_typed() {
+        for seed in 0..500 {
+            let program = generate(&mut Rng::new(seed));
+
+            assert!(crate::analyze(&program).is_ok(), "{}", program);
+        }
+    }
+
+    #[test]
+    fn uninitialised_reads() {
+        let mut rejected = 0;
+        for seed in 0..500 {
+            let (program, reads_uninitialised) =
+                generate_with_uninitialised_reads(&mut Rng::new(seed));
+
+            match crate::analyze(&program) {
+                Ok(()) => assert!(!reads_uninitialised, "{}", program),
+                Err(error) => {
+                    assert!(reads_uninitialised, "{}", program);
+                    assert!(
+                        matches!(
+                            error,
+                            crate::CompileError::Semantic {
+                                check: "variable_dec_init_and_usage"
+                            }
+                        ),
+                        "{}",
+                        program
+                    );
+                    rejected += 1;
+                }
+            }
+        }
+        assert!(rejected > 0, "no uninitialised reads");
+    }
+
+    #[test]
+    fn fuzzer_input() {
+        let program = generate(&mut Bytes(&[]));
+        assert_eq!(program.to_string(), "int main() {\n    return 0;\n}\n");
+
+        let mut rng = Rng::new(0);
+        for length in 0..200 {
+            let bytes: Vec<u8> = (0..length).map(|_| rng.below(256) as u8).collect();
+            let program = generate(&mut Bytes(&bytes));
+
+            assert!(crate::analyze(&program).is_ok(), "{}", program);
+        }
+    }
+
+    #[test]
+    fn covers_the_grammar() {
+        let code: String = (0..100)
+            .map(|seed| generate(&mut Rng::new(seed)).to_string())
+            .collect();
+
+        for snippet in [
+            " += ", " -= ", " *= ", " /= ", " %= ", " + ", " - ", " * ", " / ", " % ", " == ",
+            " != ", "-", "(", "0x", "return;", "assert(", "print(", "p7",
+        ] {
+            assert!(code.contains(snippet), "no {}", snippet);
+        }
+
+        let parenthesised = (0..100)
+            .map(|seed| generate(&mut Rng::new(seed)))
+            .filter(|program| *program != program.clone().without_parentheses())
+            .count();
+        assert!(parenthesised > 0, "no redundant parentheses");
+    }
+
+    #[test]
+    fn interpreter_agrees_with_native() {
+        let dir = TempDir::new().unwrap();
+        let executable = dir.path.join("program");
+
+        for seed in 0..8 {
+            let program = generate(&mut Rng::new(seed));
+            let code = program.to_string();
+
+            let mut output = Vec::new();
+            let interpreted = interpreter::interpret_with_io(
+                &program,
+                interpreter::Options::default(),
+                &mut &b""[..],
+                &mut output,
+            );
+
+            let asm = crate::compile_to_asm(&code, false).unwrap();
+            toolchain::link(&asm, &executable, true).unwrap();
+            let native = Command::new(&executable).output().unwrap();
+
+            let signal = std::os::unix::process::ExitStatusExt::signal(&native.status);
+            match interpreted {
+                Ok(exit_code) => {
+                    assert_eq!(native.status.code(), Some(exit_code & 0xff), "{}", code)
+                }
+                Err(Exception::Arithmetic) => assert_eq!(signal, Some(8), "{}", code),
+                Err(Exception::Abort) => assert_eq!(signal, Some(6), "{}", code),
+            }
+            assert_eq!(native.stdout, output, "{}", code);
+        }
+    }
+}
@@ -1,32 +1,99 @@
+pub mod visit;
+
+pub(crate) mod print;
+
 /// aka: The AST
 #[derive(Debug, Clone, PartialEq)]
-pub struct Program<'a>(pub Statements<'a>);
+pub struct Program<'a>(pub Vec<Function<'a>>);
+
+impl<'a> Program<'a> {
+    /// The program without any parentheses, which the structure of the AST already encodes. Two
+    /// programs which only differ in redundant parentheses are equal without them.
+    pub fn without_parentheses(self) -> Self {
+        struct Unnest;
+
+        impl<'src> visit::Folder<'src> for Unnest {
+            fn fold_lvalue(&mut self, lvalue: LValue<'src>) -> LValue<'src> {
+                match lvalue {
+                    LValue::LValue(lvalue) => self.fold_lvalue(*lvalue),
+                    lvalue => visit::fold_lvalue(self, lvalue),
+                }
+            }
+
+            fn fold_exp(&mut self, exp: Expression<'src>) -> Expression<'src> {
+                match exp {
+                    Expression::NestedExp(exp) => self.fold_exp(*exp),
+                    exp => visit::fold_exp(self, exp),
+                }
+            }
+        }
+
+        visit::Folder::fold_program(&mut Unnest, self)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function<'a> {
+    pub return_type: Type,
+    pub ident: Identifier<'a>,
+    pub params: Vec<Param<'a>>,
+    /// The contracts of the function.
+    pub annotations: Vec<Annotation<'a>>,
+    pub statements: Vec<Statement<'a>>,
+}
 
 #[derive(Debug, Clone, PartialEq)]
-pub enum Statements<'a> {
-    None,
-    Statement {
-        current: Statement<'a>,
-        next: Box<Statements<'a>>,
-    },
+pub struct Param<'a> {
+    pub ty: Type,
+    pub ident: Identifier<'a>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Statement<'a> {
     Decl(Declaration<'a>),
     Simp(SimpleInstruction<'a>),
-    Return(Expression<'a>),
+    Return(Option<Expression<'a>>),
+    Assert(Expression<'a>),
+    /// Prints an int, bool or string followed by a newline to stdout.
+    Print(Expression<'a>),
+    /// An expression whose value is thrown away, like a call of a `void` function.
+    Exp(Expression<'a>),
+    Annotations(Vec<Annotation<'a>>),
+}
+
+/// The contracts of C0 which are written as `//@<annotation>` or `/*@<annotation> @*/`.
+/// They are only checked at runtime if dynamic checking is enabled.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Annotation<'a> {
+    Requires(Expression<'a>),
+    Ensures(Expression<'a>),
+    LoopInvariant(Expression<'a>),
+    Assert(Expression<'a>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Declaration<'a> {
-    Ident(Identifier<'a>),
+    Ident {
+        ty: Type,
+        ident: Identifier<'a>,
+    },
     IdentExp {
+        ty: Type,
         ident: Identifier<'a>,
         exp: Expression<'a>,
     },
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    Int,
+    Bool,
+    Char,
+    String,
+    /// Only allowed as the return type of a function.
+    Void,
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct SimpleInstruction<'a> {
     pub lvalue: LValue<'a>,
@@ -44,8 +111,16 @@ pub enum LValue<'a> {
 pub enum Expression<'a> {
     NestedExp(Box<Expression<'a>>),
     Intconst(Intconst<'a>),
+    Boolconst(bool),
+    Charconst(Charconst<'a>),
+    Stringconst(Stringconst<'a>),
     Ident(Identifier<'a>),
 
+    Call {
+        ident: Identifier<'a>,
+        args: Vec<Expression<'a>>,
+    },
+
     Binop {
         left: Box<Expression<'a>>,
         op: BinOperation,
@@ -56,6 +131,14 @@ pub enum Expression<'a> {
         op: UnOperation,
         right: Box<Expression<'a>>,
     },
+
+    /// Reads an int from stdin.
+    Read,
+
+    /// `\result`, only allowed in `ensures` annotations.
+    Result,
+    /// `\length(e)`, only allowed in annotations.
+    Length(Box<Expression<'a>>),
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -64,12 +147,12 @@ pub enum Intconst<'a> {
     Hexnum(Hexnum<'a>),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum UnOperation {
     Minus,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AsNop {
     Equal,
     PlusEqual,
@@ -79,13 +162,15 @@ pub enum AsNop {
     ModEqual,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BinOperation {
     Plus,
     Minus,
     Multiplication,
     Division,
     Mod,
+    Equal,
+    NotEqual,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -96,3 +181,80 @@ pub struct Decnum<'a>(pub &'a str);
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct Hexnum<'a>(pub &'a str);
+
+/// The body of a character literal without the surrounding `'`, escape sequences are kept as they
+/// are in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Charconst<'a>(pub &'a str);
+
+/// The body of a string literal without the surrounding `"`, escape sequences are kept as they
+/// are in the source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stringconst<'a>(pub &'a str);
+
+impl Intconst<'_> {
+    /// The value of the constant, numbers which don't fit into an `i32` wrap around.
+    /// The range of the constant has to be checked by the `num_range` analyzer beforehand.
+    pub fn value(&self) -> i32 {
+        let value = match self {
+            Intconst::Decnum(decnum) => decnum.0.parse::<u32>(),
+            Intconst::Hexnum(hexnum) => u32::from_str_radix(hexnum.0, 16),
+        };
+
+        value.expect("checked by the num_range analyzer") as i32
+    }
+}
+
+impl Charconst<'_> {
+    pub fn value(&self) -> u8 {
+        unescape(self.0)[0]
+    }
+}
+
+impl Stringconst<'_> {
+    pub fn value(&self) -> Vec<u8> {
+        unescape(self.0)
+    }
+}
+
+impl BinOperation {
+    /// How tightly the operator binds like in `exp_parser`, the operators associate to the left.
+    /// The unary minus binds tighter than all of them.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            BinOperation::Equal | BinOperation::NotEqual => 0,
+            BinOperation::Plus | BinOperation::Minus => 1,
+            BinOperation::Multiplication | BinOperation::Division | BinOperation::Mod => 2,
+        }
+    }
+}
+
+/// Replaces the escape sequences of C0 by the characters they stand for.
+/// The parser makes sure that only valid escape sequences reach this function.
+fn unescape(literal: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(literal.len());
+
+    let mut iterator = literal.bytes();
+    while let Some(current) = iterator.next() {
+        if current != b'\\' {
+            bytes.push(current);
+            continue;
+        }
+
+        let escaped = match iterator.next() {
+            Some(b'n') => b'\n',
+            Some(b't') => b'\t',
+            Some(b'v') => 0x0b,
+            Some(b'b') => 0x08,
+            Some(b'r') => b'\r',
+            Some(b'f') => 0x0c,
+            Some(b'a') => 0x07,
+            Some(b'0') => b'\0',
+            Some(other) => other,
+            None => unreachable!("the parser doesn't allow a trailing backslash"),
+        };
+        bytes.push(escaped);
+    }
+
+    bytes
+}
@@ -0,0 +1,280 @@
+//! Prints the AST as canonical C0 code.
+//!
+//! The parentheses of the source are dropped, expressions only get the parentheses the
+//! precedence of their operators requires. Parsing the printed code therefore gives back every
+//! AST whose [`Expression::NestedExp`]s are exactly at those places, which is the case for every
+//! AST of code without redundant parentheses.
+
+use std::fmt;
+
+use super::*;
+
+impl fmt::Display for Program<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, function) in self.0.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", function)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Function<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", Header(self))?;
+        for statement in &self.statements {
+            writeln!(f, "    {}", statement)?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+/// The first line of a function up to the `{` of its body.
+pub(crate) struct Header<'f, 'src>(pub &'f Function<'src>);
+
+impl fmt::Display for Header<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let function = self.0;
+
+        write!(f, "{} {}(", function.return_type, function.ident)?;
+        for (index, param) in function.params.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{} {}", param.ty, param.ident)?;
+        }
+        write!(f, ")")?;
+
+        if !function.annotations.is_empty() {
+            write!(f, " ")?;
+            write_annotations(f, &function.annotations)?;
+        }
+
+        write!(f, " {{")
+    }
+}
+
+impl fmt::Display for Statement<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Statement::Decl(Declaration::Ident { ty, ident }) => write!(f, "{} {};", ty, ident),
+            Statement::Decl(Declaration::IdentExp { ty, ident, exp }) => {
+                write!(f, "{} {} = {};", ty, ident, exp)
+            }
+            Statement::Simp(SimpleInstruction { lvalue, asnop, exp }) => {
+                write!(f, "{} {} {};", lvalue, asnop, exp)
+            }
+            Statement::Return(None) => write!(f, "return;"),
+            Statement::Return(Some(exp)) => write!(f, "return {};", exp),
+            Statement::Assert(exp) => write!(f, "assert({});", exp),
+            Statement::Print(exp) => write!(f, "print({});", exp),
+            Statement::Exp(exp) => write!(f, "{};", exp),
+            Statement::Annotations(annotations) => write_annotations(f, annotations),
+        }
+    }
+}
+
+fn write_annotations(f: &mut fmt::Formatter<'_>, annotations: &[Annotation<'_>]) -> fmt::Result {
+    write!(f, "/*@")?;
+    for annotation in annotations {
+        match annotation {
+            Annotation::Requires(exp) => write!(f, " requires {};", exp)?,
+            Annotation::Ensures(exp) => write!(f, " ensures {};", exp)?,
+            Annotation::LoopInvariant(exp) => write!(f, " loop_invariant {};", exp)?,
+            Annotation::Assert(exp) => write!(f, " assert {};", exp)?,
+        }
+    }
+    write!(f, " @*/")
+}
+
+impl fmt::Display for LValue<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LValue::Ident(ident) => write!(f, "{}", ident),
+            LValue::LValue(lvalue) => write!(f, "{}", lvalue),
+        }
+    }
+}
+
+impl fmt::Display for Expression<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::NestedExp(exp) => write!(f, "{}", exp),
+            Expression::Intconst(Intconst::Decnum(decnum)) => write!(f, "{}", decnum.0),
+            Expression::Intconst(Intconst::Hexnum(hexnum)) => write!(f, "0x{}", hexnum.0),
+            Expression::Boolconst(boolconst) => write!(f, "{}", boolconst),
+            Expression::Charconst(charconst) => write!(f, "'{}'", charconst.0),
+            Expression::Stringconst(stringconst) => write!(f, "\"{}\"", stringconst.0),
+            Expression::Ident(ident) => write!(f, "{}", ident),
+            Expression::Call { ident, args } => {
+                write!(f, "{}(", ident)?;
+                for (index, arg) in args.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Expression::Binop { left, op, right } => {
+                write_operand(f, left, |inner| inner.precedence() < op.precedence())?;
+                write!(f, " {} ", op)?;
+                write_operand(f, right, |inner| inner.precedence() <= op.precedence())
+            }
+            Expression::Unop {
+                op: UnOperation::Minus,
+                right,
+            } => {
+                // C0 lexes `--` as a decrement
+                match unnested(right) {
+                    Expression::Unop { .. } => write!(f, "- ")?,
+                    _ => write!(f, "-")?,
+                }
+                write_operand(f, right, |_| true)
+            }
+            Expression::Read => write!(f, "read()"),
+            Expression::Result => write!(f, "\\result"),
+            Expression::Length(exp) => write!(f, "\\length({})", exp),
+        }
+    }
+}
+
+/// Writes an operand in parentheses if it's an operation which binds weaker than the operator.
+fn write_operand(
+    f: &mut fmt::Formatter<'_>,
+    exp: &Expression<'_>,
+    binds_weaker: impl Fn(BinOperation) -> bool,
+) -> fmt::Result {
+    let inner = unnested(exp);
+    match inner {
+        Expression::Binop { op, .. } if binds_weaker(*op) => write!(f, "({})", inner),
+        _ => write!(f, "{}", inner),
+    }
+}
+
+/// The expression without the parentheses around it.
+fn unnested<'a, 'src>(mut exp: &'a Expression<'src>) -> &'a Expression<'src> {
+    while let Expression::NestedExp(nested) = exp {
+        exp = nested;
+    }
+
+    exp
+}
+
+impl fmt::Display for Identifier<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Type::Int => "int",
+            Type::Bool => "bool",
+            Type::Char => "char",
+            Type::String => "string",
+            Type::Void => "void",
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for AsNop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            AsNop::Equal => "=",
+            AsNop::PlusEqual => "+=",
+            AsNop::MinusEqual => "-=",
+            AsNop::MultEqual => "*=",
+            AsNop::DivEqual => "/=",
+            AsNop::ModEqual => "%=",
+        };
+
+        write!(f, "{}", op)
+    }
+}
+
+impl fmt::Display for BinOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            BinOperation::Plus => "+",
+            BinOperation::Minus => "-",
+            BinOperation::Multiplication => "*",
+            BinOperation::Division => "/",
+            BinOperation::Mod => "%",
+            BinOperation::Equal => "==",
+            BinOperation::NotEqual => "!=",
+        };
+
+        write!(f, "{}", op)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        generator::{self, Rng},
+        parse,
+    };
+
+    fn print(code: &str) -> String {
+        parse(code).unwrap().to_string()
+    }
+
+    #[test]
+    fn functions() {
+        let code = "int f(int a, string b) /*@ requires a == 1; ensures \\result != 0; @*/ {
+    int x;
+    x = a * (a + 1) - -a;
+    x %= 0x1f;
+    return x;
+}
+
+void g() {
+    print(\"a\\n\");
+    assert(f(1, \"\") == '\\t');
+    /*@ assert \\length(\"\") == read(); @*/
+    return;
+}
+";
+
+        assert_eq!(print(code), code);
+    }
+
+    #[test]
+    fn minimal_parentheses() {
+        assert_eq!(
+            print("int main() { ((x)) = ((1 + 2)) * (3) - (4 - 5) - (-(6)); }"),
+            "int main() {\n    x = (1 + 2) * 3 - (4 - 5) - -6;\n}\n"
+        );
+        assert_eq!(
+            print("int main() { return -(1 * 2) + ((1 == 2) == (3 != 4)) + 1 % (2 / 3); }"),
+            "int main() {\n    return -(1 * 2) + (1 == 2 == (3 != 4)) + 1 % (2 / 3);\n}\n"
+        );
+        assert_eq!(
+            print("int main() { return - -f((1), (g())); }"),
+            "int main() {\n    return - -f(1, g());\n}\n"
+        );
+        assert_eq!(
+            print("int main() { return -(-(-1)) - -(2); }"),
+            "int main() {\n    return - - -1 - -2;\n}\n"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for seed in 0..100 {
+            let program = generator::generate(&mut Rng::new(seed));
+            let code = program.to_string();
+
+            let parsed = parse(&code).map(Program::without_parentheses);
+            assert_eq!(parsed.ok(), Some(program.without_parentheses()), "{}", code);
+        }
+    }
+}
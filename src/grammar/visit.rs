@@ -0,0 +1,701 @@
+//! Traversals of the AST.
+//!
+//! [`Visitor`] and [`VisitorMut`] walk over borrowed nodes and can stop with an error, [`Folder`]
+//! consumes the AST and rebuilds it. Every method defaults to walking the children of its node
+//! with the `walk_*`/`walk_*_mut`/`fold_*` function of the same node, so an implementation only
+//! overrides the nodes it's interested in and calls the walking function itself if it still
+//! wants to descend.
+
+use super::*;
+
+pub trait Visitor<'src> {
+    type Error;
+
+    fn visit_program(&mut self, program: &Program<'src>) -> Result<(), Self::Error> {
+        walk_program(self, program)
+    }
+
+    fn visit_function(&mut self, function: &Function<'src>) -> Result<(), Self::Error> {
+        walk_function(self, function)
+    }
+
+    fn visit_param(&mut self, param: &Param<'src>) -> Result<(), Self::Error> {
+        walk_param(self, param)
+    }
+
+    fn visit_annotation(&mut self, annotation: &Annotation<'src>) -> Result<(), Self::Error> {
+        walk_annotation(self, annotation)
+    }
+
+    fn visit_statement(&mut self, statement: &Statement<'src>) -> Result<(), Self::Error> {
+        walk_statement(self, statement)
+    }
+
+    fn visit_decl(&mut self, decl: &Declaration<'src>) -> Result<(), Self::Error> {
+        walk_decl(self, decl)
+    }
+
+    fn visit_simp(&mut self, simp: &SimpleInstruction<'src>) -> Result<(), Self::Error> {
+        walk_simp(self, simp)
+    }
+
+    fn visit_lvalue(&mut self, lvalue: &LValue<'src>) -> Result<(), Self::Error> {
+        walk_lvalue(self, lvalue)
+    }
+
+    fn visit_exp(&mut self, exp: &Expression<'src>) -> Result<(), Self::Error> {
+        walk_exp(self, exp)
+    }
+
+    fn visit_ident(&mut self, _ident: &Identifier<'src>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn visit_intconst(&mut self, _intconst: &Intconst<'src>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+pub fn walk_program<'src, V: Visitor<'src> + ?Sized>(
+    visitor: &mut V,
+    program: &Program<'src>,
+) -> Result<(), V::Error> {
+    for function in &program.0 {
+        visitor.visit_function(function)?;
+    }
+
+    Ok(())
+}
+
+/// Visits the parameters, then the contracts and then the body.
+pub fn walk_function<'src, V: Visitor<'src> + ?Sized>(
+    visitor: &mut V,
+    function: &Function<'src>,
+) -> Result<(), V::Error> {
+    visitor.visit_ident(&function.ident)?;
+    for param in &function.params {
+        visitor.visit_param(param)?;
+    }
+    for annotation in &function.annotations {
+        visitor.visit_annotation(annotation)?;
+    }
+    for statement in &function.statements {
+        visitor.visit_statement(statement)?;
+    }
+
+    Ok(())
+}
+
+pub fn walk_param<'src, V: Visitor<'src> + ?Sized>(
+    visitor: &mut V,
+    param: &Param<'src>,
+) -> Result<(), V::Error> {
+    visitor.visit_ident(&param.ident)
+}
+
+pub fn walk_annotation<'src, V: Visitor<'src> + ?Sized>(
+    visitor: &mut V,
+    annotation: &Annotation<'src>,
+) -> Result<(), V::Error> {
+    match annotation {
+        Annotation::Requires(exp)
+        | Annotation::Ensures(exp)
+        | Annotation::LoopInvariant(exp)
+        | Annotation::Assert(exp) => visitor.visit_exp(exp),
+    }
+}
+
+pub fn walk_statement<'src, V: Visitor<'src> + ?Sized>(
+    visitor: &mut V,
+    statement: &Statement<'src>,
+) -> Result<(), V::Error> {
+    match statement {
+        Statement::Decl(decl) => visitor.visit_decl(decl),
+        Statement::Simp(simp) => visitor.visit_simp(simp),
+        Statement::Return(None) => Ok(()),
+        Statement::Return(Some(exp))
+        | Statement::Assert(exp)
+        | Statement::Print(exp)
+        | Statement::Exp(exp) => visitor.visit_exp(exp),
+        Statement::Annotations(annotations) => {
+            for annotation in annotations {
+                visitor.visit_annotation(annotation)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+pub fn walk_decl<'src, V: Visitor<'src> + ?Sized>(
+    visitor: &mut V,
+    decl: &Declaration<'src>,
+) -> Result<(), V::Error> {
+    match decl {
+        Declaration::Ident { ident, .. } => visitor.visit_ident(ident),
+        Declaration::IdentExp { ident, exp, .. } => {
+            visitor.visit_ident(ident)?;
+            visitor.visit_exp(exp)
+        }
+    }
+}
+
+pub fn walk_simp<'src, V: Visitor<'src> + ?Sized>(
+    visitor: &mut V,
+    simp: &SimpleInstruction<'src>,
+) -> Result<(), V::Error> {
+    visitor.visit_lvalue(&simp.lvalue)?;
+    visitor.visit_exp(&simp.exp)
+}
+
+pub fn walk_lvalue<'src, V: Visitor<'src> + ?Sized>(
+    visitor: &mut V,
+    lvalue: &LValue<'src>,
+) -> Result<(), V::Error> {
+    match lvalue {
+        LValue::Ident(ident) => visitor.visit_ident(ident),
+        LValue::LValue(lvalue) => visitor.visit_lvalue(lvalue),
+    }
+}
+
+pub fn walk_exp<'src, V: Visitor<'src> + ?Sized>(
+    visitor: &mut V,
+    exp: &Expression<'src>,
+) -> Result<(), V::Error> {
+    match exp {
+        Expression::NestedExp(exp)
+        | Expression::Unop { right: exp, .. }
+        | Expression::Length(exp) => visitor.visit_exp(exp),
+        Expression::Intconst(intconst) => visitor.visit_intconst(intconst),
+        Expression::Boolconst(_)
+        | Expression::Charconst(_)
+        | Expression::Stringconst(_)
+        | Expression::Read
+        | Expression::Result => Ok(()),
+        Expression::Ident(ident) => visitor.visit_ident(ident),
+        Expression::Call { ident, args } => {
+            visitor.visit_ident(ident)?;
+            for arg in args {
+                visitor.visit_exp(arg)?;
+            }
+
+            Ok(())
+        }
+        Expression::Binop { left, right, .. } => {
+            visitor.visit_exp(left)?;
+            visitor.visit_exp(right)
+        }
+    }
+}
+
+pub trait VisitorMut<'src> {
+    type Error;
+
+    fn visit_program_mut(&mut self, program: &mut Program<'src>) -> Result<(), Self::Error> {
+        walk_program_mut(self, program)
+    }
+
+    fn visit_function_mut(&mut self, function: &mut Function<'src>) -> Result<(), Self::Error> {
+        walk_function_mut(self, function)
+    }
+
+    fn visit_param_mut(&mut self, param: &mut Param<'src>) -> Result<(), Self::Error> {
+        walk_param_mut(self, param)
+    }
+
+    fn visit_annotation_mut(
+        &mut self,
+        annotation: &mut Annotation<'src>,
+    ) -> Result<(), Self::Error> {
+        walk_annotation_mut(self, annotation)
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement<'src>) -> Result<(), Self::Error> {
+        walk_statement_mut(self, statement)
+    }
+
+    fn visit_decl_mut(&mut self, decl: &mut Declaration<'src>) -> Result<(), Self::Error> {
+        walk_decl_mut(self, decl)
+    }
+
+    fn visit_simp_mut(&mut self, simp: &mut SimpleInstruction<'src>) -> Result<(), Self::Error> {
+        walk_simp_mut(self, simp)
+    }
+
+    fn visit_lvalue_mut(&mut self, lvalue: &mut LValue<'src>) -> Result<(), Self::Error> {
+        walk_lvalue_mut(self, lvalue)
+    }
+
+    fn visit_exp_mut(&mut self, exp: &mut Expression<'src>) -> Result<(), Self::Error> {
+        walk_exp_mut(self, exp)
+    }
+
+    fn visit_ident_mut(&mut self, _ident: &mut Identifier<'src>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn visit_intconst_mut(&mut self, _intconst: &mut Intconst<'src>) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+pub fn walk_program_mut<'src, V: VisitorMut<'src> + ?Sized>(
+    visitor: &mut V,
+    program: &mut Program<'src>,
+) -> Result<(), V::Error> {
+    for function in &mut program.0 {
+        visitor.visit_function_mut(function)?;
+    }
+
+    Ok(())
+}
+
+pub fn walk_function_mut<'src, V: VisitorMut<'src> + ?Sized>(
+    visitor: &mut V,
+    function: &mut Function<'src>,
+) -> Result<(), V::Error> {
+    visitor.visit_ident_mut(&mut function.ident)?;
+    for param in &mut function.params {
+        visitor.visit_param_mut(param)?;
+    }
+    for annotation in &mut function.annotations {
+        visitor.visit_annotation_mut(annotation)?;
+    }
+    for statement in &mut function.statements {
+        visitor.visit_statement_mut(statement)?;
+    }
+
+    Ok(())
+}
+
+pub fn walk_param_mut<'src, V: VisitorMut<'src> + ?Sized>(
+    visitor: &mut V,
+    param: &mut Param<'src>,
+) -> Result<(), V::Error> {
+    visitor.visit_ident_mut(&mut param.ident)
+}
+
+pub fn walk_annotation_mut<'src, V: VisitorMut<'src> + ?Sized>(
+    visitor: &mut V,
+    annotation: &mut Annotation<'src>,
+) -> Result<(), V::Error> {
+    match annotation {
+        Annotation::Requires(exp)
+        | Annotation::Ensures(exp)
+        | Annotation::LoopInvariant(exp)
+        | Annotation::Assert(exp) => visitor.visit_exp_mut(exp),
+    }
+}
+
+pub fn walk_statement_mut<'src, V: VisitorMut<'src> + ?Sized>(
+    visitor: &mut V,
+    statement: &mut Statement<'src>,
+) -> Result<(), V::Error> {
+    match statement {
+        Statement::Decl(decl) => visitor.visit_decl_mut(decl),
+        Statement::Simp(simp) => visitor.visit_simp_mut(simp),
+        Statement::Return(None) => Ok(()),
+        Statement::Return(Some(exp))
+        | Statement::Assert(exp)
+        | Statement::Print(exp)
+        | Statement::Exp(exp) => visitor.visit_exp_mut(exp),
+        Statement::Annotations(annotations) => {
+            for annotation in annotations {
+                visitor.visit_annotation_mut(annotation)?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+pub fn walk_decl_mut<'src, V: VisitorMut<'src> + ?Sized>(
+    visitor: &mut V,
+    decl: &mut Declaration<'src>,
+) -> Result<(), V::Error> {
+    match decl {
+        Declaration::Ident { ident, .. } => visitor.visit_ident_mut(ident),
+        Declaration::IdentExp { ident, exp, .. } => {
+            visitor.visit_ident_mut(ident)?;
+            visitor.visit_exp_mut(exp)
+        }
+    }
+}
+
+pub fn walk_simp_mut<'src, V: VisitorMut<'src> + ?Sized>(
+    visitor: &mut V,
+    simp: &mut SimpleInstruction<'src>,
+) -> Result<(), V::Error> {
+    visitor.visit_lvalue_mut(&mut simp.lvalue)?;
+    visitor.visit_exp_mut(&mut simp.exp)
+}
+
+pub fn walk_lvalue_mut<'src, V: VisitorMut<'src> + ?Sized>(
+    visitor: &mut V,
+    lvalue: &mut LValue<'src>,
+) -> Result<(), V::Error> {
+    match lvalue {
+        LValue::Ident(ident) => visitor.visit_ident_mut(ident),
+        LValue::LValue(lvalue) => visitor.visit_lvalue_mut(lvalue),
+    }
+}
+
+pub fn walk_exp_mut<'src, V: VisitorMut<'src> + ?Sized>(
+    visitor: &mut V,
+    exp: &mut Expression<'src>,
+) -> Result<(), V::Error> {
+    match exp {
+        Expression::NestedExp(exp)
+        | Expression::Unop { right: exp, .. }
+        | Expression::Length(exp) => visitor.visit_exp_mut(exp),
+        Expression::Intconst(intconst) => visitor.visit_intconst_mut(intconst),
+        Expression::Boolconst(_)
+        | Expression::Charconst(_)
+        | Expression::Stringconst(_)
+        | Expression::Read
+        | Expression::Result => Ok(()),
+        Expression::Ident(ident) => visitor.visit_ident_mut(ident),
+        Expression::Call { ident, args } => {
+            visitor.visit_ident_mut(ident)?;
+            for arg in args {
+                visitor.visit_exp_mut(arg)?;
+            }
+
+            Ok(())
+        }
+        Expression::Binop { left, right, .. } => {
+            visitor.visit_exp_mut(left)?;
+            visitor.visit_exp_mut(right)
+        }
+    }
+}
+
+/// Rebuilds the AST bottom-up, e.g. to simplify expressions.
+pub trait Folder<'src> {
+    fn fold_program(&mut self, program: Program<'src>) -> Program<'src> {
+        fold_program(self, program)
+    }
+
+    fn fold_function(&mut self, function: Function<'src>) -> Function<'src> {
+        fold_function(self, function)
+    }
+
+    fn fold_param(&mut self, param: Param<'src>) -> Param<'src> {
+        fold_param(self, param)
+    }
+
+    fn fold_annotation(&mut self, annotation: Annotation<'src>) -> Annotation<'src> {
+        fold_annotation(self, annotation)
+    }
+
+    /// Folds a function body, which allows statements to be removed or added.
+    fn fold_statements(&mut self, statements: Vec<Statement<'src>>) -> Vec<Statement<'src>> {
+        fold_statements(self, statements)
+    }
+
+    fn fold_statement(&mut self, statement: Statement<'src>) -> Statement<'src> {
+        fold_statement(self, statement)
+    }
+
+    fn fold_decl(&mut self, decl: Declaration<'src>) -> Declaration<'src> {
+        fold_decl(self, decl)
+    }
+
+    fn fold_simp(&mut self, simp: SimpleInstruction<'src>) -> SimpleInstruction<'src> {
+        fold_simp(self, simp)
+    }
+
+    fn fold_lvalue(&mut self, lvalue: LValue<'src>) -> LValue<'src> {
+        fold_lvalue(self, lvalue)
+    }
+
+    fn fold_exp(&mut self, exp: Expression<'src>) -> Expression<'src> {
+        fold_exp(self, exp)
+    }
+
+    fn fold_ident(&mut self, ident: Identifier<'src>) -> Identifier<'src> {
+        ident
+    }
+
+    fn fold_intconst(&mut self, intconst: Intconst<'src>) -> Intconst<'src> {
+        intconst
+    }
+}
+
+pub fn fold_program<'src, F: Folder<'src> + ?Sized>(
+    folder: &mut F,
+    program: Program<'src>,
+) -> Program<'src> {
+    Program(
+        program
+            .0
+            .into_iter()
+            .map(|function| folder.fold_function(function))
+            .collect(),
+    )
+}
+
+pub fn fold_function<'src, F: Folder<'src> + ?Sized>(
+    folder: &mut F,
+    function: Function<'src>,
+) -> Function<'src> {
+    let Function {
+        return_type,
+        ident,
+        params,
+        annotations,
+        statements,
+    } = function;
+
+    Function {
+        return_type,
+        ident: folder.fold_ident(ident),
+        params: params
+            .into_iter()
+            .map(|param| folder.fold_param(param))
+            .collect(),
+        annotations: annotations
+            .into_iter()
+            .map(|annotation| folder.fold_annotation(annotation))
+            .collect(),
+        statements: folder.fold_statements(statements),
+    }
+}
+
+pub fn fold_param<'src, F: Folder<'src> + ?Sized>(
+    folder: &mut F,
+    param: Param<'src>,
+) -> Param<'src> {
+    Param {
+        ty: param.ty,
+        ident: folder.fold_ident(param.ident),
+    }
+}
+
+pub fn fold_annotation<'src, F: Folder<'src> + ?Sized>(
+    folder: &mut F,
+    annotation: Annotation<'src>,
+) -> Annotation<'src> {
+    match annotation {
+        Annotation::Requires(exp) => Annotation::Requires(folder.fold_exp(exp)),
+        Annotation::Ensures(exp) => Annotation::Ensures(folder.fold_exp(exp)),
+        Annotation::LoopInvariant(exp) => Annotation::LoopInvariant(folder.fold_exp(exp)),
+        Annotation::Assert(exp) => Annotation::Assert(folder.fold_exp(exp)),
+    }
+}
+
+pub fn fold_statements<'src, F: Folder<'src> + ?Sized>(
+    folder: &mut F,
+    statements: Vec<Statement<'src>>,
+) -> Vec<Statement<'src>> {
+    statements
+        .into_iter()
+        .map(|statement| folder.fold_statement(statement))
+        .collect()
+}
+
+pub fn fold_statement<'src, F: Folder<'src> + ?Sized>(
+    folder: &mut F,
+    statement: Statement<'src>,
+) -> Statement<'src> {
+    match statement {
+        Statement::Decl(decl) => Statement::Decl(folder.fold_decl(decl)),
+        Statement::Simp(simp) => Statement::Simp(folder.fold_simp(simp)),
+        Statement::Return(exp) => Statement::Return(exp.map(|exp| folder.fold_exp(exp))),
+        Statement::Assert(exp) => Statement::Assert(folder.fold_exp(exp)),
+        Statement::Print(exp) => Statement::Print(folder.fold_exp(exp)),
+        Statement::Exp(exp) => Statement::Exp(folder.fold_exp(exp)),
+        Statement::Annotations(annotations) => Statement::Annotations(
+            annotations
+                .into_iter()
+                .map(|annotation| folder.fold_annotation(annotation))
+                .collect(),
+        ),
+    }
+}
+
+pub fn fold_decl<'src, F: Folder<'src> + ?Sized>(
+    folder: &mut F,
+    decl: Declaration<'src>,
+) -> Declaration<'src> {
+    match decl {
+        Declaration::Ident { ty, ident } => Declaration::Ident {
+            ty,
+            ident: folder.fold_ident(ident),
+        },
+        Declaration::IdentExp { ty, ident, exp } => Declaration::IdentExp {
+            ty,
+            ident: folder.fold_ident(ident),
+            exp: folder.fold_exp(exp),
+        },
+    }
+}
+
+pub fn fold_simp<'src, F: Folder<'src> + ?Sized>(
+    folder: &mut F,
+    simp: SimpleInstruction<'src>,
+) -> SimpleInstruction<'src> {
+    SimpleInstruction {
+        lvalue: folder.fold_lvalue(simp.lvalue),
+        asnop: simp.asnop,
+        exp: folder.fold_exp(simp.exp),
+    }
+}
+
+pub fn fold_lvalue<'src, F: Folder<'src> + ?Sized>(
+    folder: &mut F,
+    lvalue: LValue<'src>,
+) -> LValue<'src> {
+    match lvalue {
+        LValue::Ident(ident) => LValue::Ident(folder.fold_ident(ident)),
+        LValue::LValue(lvalue) => LValue::LValue(Box::new(folder.fold_lvalue(*lvalue))),
+    }
+}
+
+pub fn fold_exp<'src, F: Folder<'src> + ?Sized>(
+    folder: &mut F,
+    exp: Expression<'src>,
+) -> Expression<'src> {
+    match exp {
+        Expression::NestedExp(exp) => Expression::NestedExp(Box::new(folder.fold_exp(*exp))),
+        Expression::Intconst(intconst) => Expression::Intconst(folder.fold_intconst(intconst)),
+        Expression::Ident(ident) => Expression::Ident(folder.fold_ident(ident)),
+        Expression::Call { ident, args } => Expression::Call {
+            ident: folder.fold_ident(ident),
+            args: args.into_iter().map(|arg| folder.fold_exp(arg)).collect(),
+        },
+        Expression::Binop { left, op, right } => Expression::Binop {
+            left: Box::new(folder.fold_exp(*left)),
+            op,
+            right: Box::new(folder.fold_exp(*right)),
+        },
+        Expression::Unop { op, right } => Expression::Unop {
+            op,
+            right: Box::new(folder.fold_exp(*right)),
+        },
+        Expression::Length(exp) => Expression::Length(Box::new(folder.fold_exp(*exp))),
+        exp @ (Expression::Boolconst(_)
+        | Expression::Charconst(_)
+        | Expression::Stringconst(_)
+        | Expression::Read
+        | Expression::Result) => exp,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn program(code: &str) -> Program<'_> {
+        crate::parse(code).unwrap()
+    }
+
+    /// Collects every identifier in the order it's visited.
+    #[derive(Default)]
+    struct Idents<'src>(Vec<&'src str>);
+
+    impl<'src> Visitor<'src> for Idents<'src> {
+        type Error = ();
+
+        fn visit_ident(&mut self, ident: &Identifier<'src>) -> Result<(), ()> {
+            self.0.push(ident.0);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn visit_order() {
+        let program =
+            program("int f(int a) //@requires a == b;\n { int c = g(d); (e) = -h; return i; }");
+        let mut idents = Idents::default();
+        idents.visit_program(&program).unwrap();
+
+        assert_eq!(
+            idents.0,
+            vec!["f", "a", "a", "b", "c", "g", "d", "e", "h", "i"]
+        );
+    }
+
+    /// Stops at the first `read()`.
+    struct NoRead;
+
+    impl<'src> Visitor<'src> for NoRead {
+        type Error = &'static str;
+
+        fn visit_exp(&mut self, exp: &Expression<'src>) -> Result<(), Self::Error> {
+            match exp {
+                Expression::Read => Err("read"),
+                _ => walk_exp(self, exp),
+            }
+        }
+    }
+
+    #[test]
+    fn visit_error() {
+        let program = program("int main() { print(1 + -(read())); return 0; }");
+
+        assert_eq!(NoRead.visit_program(&program), Err("read"));
+    }
+
+    struct Rename;
+
+    impl<'src> VisitorMut<'src> for Rename {
+        type Error = ();
+
+        fn visit_ident_mut(&mut self, ident: &mut Identifier<'src>) -> Result<(), ()> {
+            if ident.0 == "x" {
+                ident.0 = "y";
+            }
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn visit_mut_rename() {
+        let mut renamed = program("int main() { int x = 1; x += x; return x; }");
+        Rename.visit_program_mut(&mut renamed).unwrap();
+
+        assert_eq!(
+            renamed,
+            program("int main() { int y = 1; y += y; return y; }")
+        );
+    }
+
+    /// Rewrites `-1` as `0 - 1` and drops assertions.
+    struct Simplify;
+
+    impl<'src> Folder<'src> for Simplify {
+        fn fold_statements(&mut self, statements: Vec<Statement<'src>>) -> Vec<Statement<'src>> {
+            let statements = statements
+                .into_iter()
+                .filter(|statement| !matches!(statement, Statement::Assert(_)))
+                .collect();
+
+            fold_statements(self, statements)
+        }
+
+        fn fold_exp(&mut self, exp: Expression<'src>) -> Expression<'src> {
+            match fold_exp(self, exp) {
+                Expression::Unop {
+                    op: UnOperation::Minus,
+                    right,
+                } if matches!(*right, Expression::Intconst(Intconst::Decnum(Decnum("1")))) => {
+                    Expression::Binop {
+                        left: Box::new(Expression::Intconst(Intconst::Decnum(Decnum("0")))),
+                        op: BinOperation::Minus,
+                        right,
+                    }
+                }
+                exp => exp,
+            }
+        }
+    }
+
+    #[test]
+    fn fold_rebuild() {
+        let folded = Simplify.fold_program(program("int main() { assert(true); return (-1); }"));
+
+        assert_eq!(folded, program("int main() { return (0 - 1); }"));
+    }
+}
@@ -0,0 +1,148 @@
+//! The heap of the interpreter with a mark-and-sweep garbage collector.
+//! Strings are the only objects on the heap and they don't refer to other objects, so marking
+//! boils down to flagging the objects which are reachable from the roots.
+
+/// The heap doesn't collect before this many bytes have been allocated.
+const MIN_THRESHOLD: usize = 1 << 16;
+
+/// A reference to an object on the heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Handle(usize);
+
+#[derive(Debug)]
+struct Object {
+    bytes: Box<[u8]>,
+    marked: bool,
+}
+
+#[derive(Debug)]
+pub struct Heap {
+    /// `None` marks a slot which is free for the next allocation.
+    objects: Vec<Option<Object>>,
+    free: Vec<usize>,
+    allocated_bytes: usize,
+    /// A collection runs once `allocated_bytes` reaches this.
+    threshold: usize,
+    garbage_collection: bool,
+}
+
+impl Heap {
+    /// Creates an empty heap which never frees anything if `garbage_collection` is disabled.
+    pub fn new(garbage_collection: bool) -> Self {
+        Self {
+            objects: Vec::new(),
+            free: Vec::new(),
+            allocated_bytes: 0,
+            threshold: MIN_THRESHOLD,
+            garbage_collection,
+        }
+    }
+
+    pub fn alloc(&mut self, bytes: Vec<u8>) -> Handle {
+        self.allocated_bytes += bytes.len();
+
+        let object = Some(Object {
+            bytes: bytes.into_boxed_slice(),
+            marked: false,
+        });
+
+        match self.free.pop() {
+            Some(index) => {
+                self.objects[index] = object;
+                Handle(index)
+            }
+            None => {
+                self.objects.push(object);
+                Handle(self.objects.len() - 1)
+            }
+        }
+    }
+
+    pub fn get(&self, handle: Handle) -> &[u8] {
+        &self.objects[handle.0]
+            .as_ref()
+            .expect("handles of reachable objects stay valid")
+            .bytes
+    }
+
+    /// Tells if enough has been allocated since the last collection to collect again.
+    pub fn should_collect(&self) -> bool {
+        self.garbage_collection && self.allocated_bytes >= self.threshold
+    }
+
+    /// Frees every object which isn't reachable from the given roots.
+    pub fn collect(&mut self, roots: impl IntoIterator<Item = Handle>) {
+        for root in roots {
+            if let Some(object) = &mut self.objects[root.0] {
+                object.marked = true;
+            }
+        }
+
+        self.allocated_bytes = 0;
+        for (index, slot) in self.objects.iter_mut().enumerate() {
+            match slot {
+                Some(object) if object.marked => {
+                    object.marked = false;
+                    self.allocated_bytes += object.bytes.len();
+                }
+                Some(_) => {
+                    *slot = None;
+                    self.free.push(index);
+                }
+                None => {}
+            }
+        }
+
+        self.threshold = MIN_THRESHOLD.max(2 * self.allocated_bytes);
+    }
+
+    /// The number of objects which haven't been freed yet.
+    #[cfg(test)]
+    pub fn len(&self) -> usize {
+        self.objects.len() - self.free.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn collect_unreachable() {
+        let mut heap = Heap::new(true);
+        let kept = heap.alloc(b"kept".to_vec());
+        heap.alloc(b"garbage".to_vec());
+
+        heap.collect([kept]);
+
+        assert_eq!(heap.len(), 1);
+        assert_eq!(heap.get(kept), b"kept");
+    }
+
+    #[test]
+    fn reuse_freed_slots() {
+        let mut heap = Heap::new(true);
+        heap.alloc(b"garbage".to_vec());
+        heap.collect([]);
+
+        assert_eq!(heap.alloc(b"new".to_vec()), Handle(0));
+    }
+
+    #[test]
+    fn threshold() {
+        let mut heap = Heap::new(true);
+        heap.alloc(vec![0; MIN_THRESHOLD - 1]);
+        assert!(!heap.should_collect());
+
+        heap.alloc(vec![0]);
+        assert!(heap.should_collect());
+    }
+
+    #[test]
+    fn disabled() {
+        let mut heap = Heap::new(false);
+        heap.alloc(vec![0; MIN_THRESHOLD]);
+
+        assert!(!heap.should_collect());
+    }
+}
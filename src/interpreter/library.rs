@@ -0,0 +1,81 @@
+use std::cmp::Ordering;
+
+use super::{Exception, Value, heap::Heap};
+use crate::library::Builtin;
+
+/// Calls the builtin with the already type checked arguments.
+/// Violated preconditions of the library functions abort the program like in the C0 library.
+/// New strings are allocated on the given heap.
+pub fn call(builtin: Builtin, args: &[Value], heap: &mut Heap) -> Result<Value, Exception> {
+    let string = |index: usize| heap.get(args[index].string());
+
+    let value = match builtin {
+        Builtin::StringLength => Value::Int(string(0).len() as i32),
+        Builtin::StringCharat => {
+            let string = string(0);
+            let index = checked_index(args[1].int(), string.len())?;
+            if index == string.len() {
+                return Err(Exception::Abort);
+            }
+
+            Value::Char(string[index])
+        }
+        Builtin::StringJoin => {
+            let joined = [string(0), string(1)].concat();
+            Value::String(heap.alloc(joined))
+        }
+        Builtin::StringSub => {
+            let string = string(0);
+            let start = checked_index(args[1].int(), string.len())?;
+            let end = checked_index(args[2].int(), string.len())?;
+            if start > end {
+                return Err(Exception::Abort);
+            }
+
+            let sub = string[start..end].to_vec();
+            Value::String(heap.alloc(sub))
+        }
+        Builtin::StringEqual => Value::Bool(string(0) == string(1)),
+        Builtin::StringCompare => {
+            let ordering = match string(0).cmp(string(1)) {
+                Ordering::Less => -1,
+                Ordering::Equal => 0,
+                Ordering::Greater => 1,
+            };
+
+            Value::Int(ordering)
+        }
+        Builtin::StringFromint => {
+            let string = args[0].int().to_string().into_bytes();
+            Value::String(heap.alloc(string))
+        }
+        Builtin::StringFromchar => {
+            let char = args[0].char();
+            if char == b'\0' {
+                return Err(Exception::Abort);
+            }
+
+            Value::String(heap.alloc(vec![char]))
+        }
+        Builtin::CharOrd => Value::Int(args[0].char() as i32),
+        Builtin::CharChr => {
+            let ord = args[0].int();
+            if !(0..=127).contains(&ord) {
+                return Err(Exception::Abort);
+            }
+
+            Value::Char(ord as u8)
+        }
+    };
+
+    Ok(value)
+}
+
+/// Converts `index` into an index of a string with the given length.
+/// The length itself is a valid index since it's the end of the string.
+fn checked_index(index: i32, len: usize) -> Result<usize, Exception> {
+    usize::try_from(index)
+        .ok()
+        .filter(|index| *index <= len)
+        .ok_or(Exception::Abort)
+}
@@ -0,0 +1,722 @@
+//! A reference interpreter which runs the AST directly.
+//! It runs programs with `--interpret` and serves as the reference for the native backend.
+mod heap;
+mod library;
+
+use std::{
+    collections::HashMap,
+    ffi::c_int,
+    io::{BufRead, Write},
+};
+
+use crate::{grammar::*, library::Builtin};
+use heap::{Handle, Heap};
+
+const SIGABRT: c_int = 6;
+const SIGFPE: c_int = 8;
+
+unsafe extern "C" {
+    fn raise(signal: c_int) -> c_int;
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Options {
+    /// Check the annotations of the program.
+    pub dynamic_checks: bool,
+    /// Free unreachable strings, can be disabled for debugging.
+    pub garbage_collection: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            dynamic_checks: false,
+            garbage_collection: true,
+        }
+    }
+}
+
+/// Runs the program and returns the value returned by `main`.
+/// The program has to pass the analyzers beforehand.
+pub fn interpret<'src>(program: &Program<'src>, options: Options) -> Result<i32, Exception> {
+    let mut stdin = std::io::stdin().lock();
+    let mut stdout = std::io::stdout().lock();
+
+    interpret_with_io(program, options, &mut stdin, &mut stdout)
+}
+
+/// Like [interpret] but `read` and `print` use the given input and output.
+pub fn interpret_with_io<'src>(
+    program: &Program<'src>,
+    options: Options,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<i32, Exception> {
+    let mut interpreter = Interpreter::new(program, options, input, output);
+    interpreter.run()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int(i32),
+    Bool(bool),
+    Char(u8),
+    /// Strings are immutable in C0, so they can be shared between variables.
+    /// They contain ASCII characters only and never a `\0`.
+    String(Handle),
+}
+
+impl Value {
+    fn int(&self) -> i32 {
+        match self {
+            Value::Int(int) => *int,
+            _ => unreachable!("checked by the type_check analyzer"),
+        }
+    }
+
+    fn char(&self) -> u8 {
+        match self {
+            Value::Char(char) => *char,
+            _ => unreachable!("checked by the type_check analyzer"),
+        }
+    }
+
+    fn string(&self) -> Handle {
+        match self {
+            Value::String(string) => *string,
+            _ => unreachable!("checked by the type_check analyzer"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exception {
+    /// A division by zero or an overflowing division which raises `SIGFPE`.
+    Arithmetic,
+    /// A failed runtime check which raises `SIGABRT`.
+    Abort,
+}
+
+impl Exception {
+    /// Terminates the process with the signal the compiled program would receive.
+    pub fn raise(self) -> ! {
+        let signal = match self {
+            Exception::Arithmetic => SIGFPE,
+            Exception::Abort => SIGABRT,
+        };
+
+        unsafe {
+            raise(signal);
+        }
+
+        std::process::abort()
+    }
+}
+
+/// Tells whether the statements after a statement should be run.
+#[derive(Debug)]
+enum Flow {
+    Next,
+    Return(Option<Value>),
+}
+
+struct Interpreter<'a, 'src> {
+    functions: HashMap<&'src str, &'a Function<'src>>,
+    input: &'a mut dyn BufRead,
+    output: &'a mut dyn Write,
+    /// The variables of every running function, the last one belongs to the current function.
+    frames: Vec<HashMap<&'src str, Value>>,
+    /// Evaluated arguments of calls which haven't been made yet.
+    temporaries: Vec<Value>,
+    heap: Heap,
+    dynamic_checks: bool,
+    /// The values of `\result` of the functions whose `ensures` annotations are being checked,
+    /// the last one belongs to the innermost call. They're roots, since the annotations may call
+    /// functions which collect garbage.
+    results: Vec<Option<Value>>,
+}
+
+impl<'a, 'src> Interpreter<'a, 'src> {
+    fn new(
+        program: &'a Program<'src>,
+        options: Options,
+        input: &'a mut dyn BufRead,
+        output: &'a mut dyn Write,
+    ) -> Self {
+        let functions = program
+            .0
+            .iter()
+            .map(|function| (function.ident.0, function))
+            .collect();
+
+        Self {
+            functions,
+            input,
+            output,
+            frames: Vec::new(),
+            temporaries: Vec::new(),
+            heap: Heap::new(options.garbage_collection),
+            dynamic_checks: options.dynamic_checks,
+            results: Vec::new(),
+        }
+    }
+
+    fn run(&mut self) -> Result<i32, Exception> {
+        let value = self
+            .call("main", vec![])?
+            .expect("checked by the type_check analyzer");
+
+        Ok(value.int())
+    }
+
+    fn call(&mut self, ident: &str, args: Vec<Value>) -> Result<Option<Value>, Exception> {
+        let function = self.functions[ident];
+
+        let params = function.params.iter().map(|param| param.ident.0);
+        self.frames.push(params.zip(args).collect());
+
+        for annotation in &function.annotations {
+            if let Annotation::Requires(exp) = annotation {
+                self.check(exp)?;
+            }
+        }
+
+        let value = match self.run_statements(&function.statements)? {
+            Flow::Return(value) => value,
+            // only `void` functions may run off their end
+            Flow::Next => None,
+        };
+
+        // the ensures annotations might call functions with their own `\result`
+        self.results.push(value);
+        for annotation in &function.annotations {
+            if let Annotation::Ensures(exp) = annotation {
+                self.check(exp)?;
+            }
+        }
+        self.results.pop();
+
+        self.frames.pop();
+        Ok(value)
+    }
+
+    fn variables(&mut self) -> &mut HashMap<&'src str, Value> {
+        self.frames.last_mut().expect("a function is running")
+    }
+
+    /// Frees the strings which can't be reached anymore.
+    /// Intermediate values of expressions aren't roots, so this may only run between statements.
+    fn collect_garbage(&mut self) {
+        let variables = self.frames.iter().flat_map(|frame| frame.values());
+        let roots = variables
+            .chain(&self.temporaries)
+            .chain(self.results.iter().flatten())
+            .filter_map(|value| match value {
+                Value::String(handle) => Some(*handle),
+                _ => None,
+            });
+
+        self.heap.collect(roots);
+    }
+
+    /// Aborts if dynamic checking is enabled and the annotation doesn't hold.
+    fn check(&mut self, exp: &Expression<'src>) -> Result<(), Exception> {
+        if !self.dynamic_checks {
+            return Ok(());
+        }
+
+        match self.eval_exp(exp)? {
+            Value::Bool(true) => Ok(()),
+            _ => Err(Exception::Abort),
+        }
+    }
+
+    fn run_statements(&mut self, statements: &[Statement<'src>]) -> Result<Flow, Exception> {
+        for statement in statements {
+            match self.run_statement(statement)? {
+                Flow::Next => {}
+                flow => return Ok(flow),
+            }
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn run_statement(&mut self, statement: &Statement<'src>) -> Result<Flow, Exception> {
+        if self.heap.should_collect() {
+            self.collect_garbage();
+        }
+
+        match statement {
+            Statement::Decl(decl) => self.run_decl(decl)?,
+            Statement::Simp(simp) => self.run_simp(simp)?,
+            Statement::Return(None) => return Ok(Flow::Return(None)),
+            Statement::Return(Some(exp)) => return Ok(Flow::Return(Some(self.eval_exp(exp)?))),
+            Statement::Assert(exp) => {
+                if self.eval_exp(exp)? != Value::Bool(true) {
+                    return Err(Exception::Abort);
+                }
+            }
+            Statement::Print(exp) => {
+                let value = self.eval_exp(exp)?;
+                self.print(&value).map_err(|_| Exception::Abort)?;
+            }
+            // `void` functions can only be called here
+            Statement::Exp(Expression::Call { ident, args }) => {
+                self.eval_call(ident, args)?;
+            }
+            Statement::Exp(exp) => {
+                self.eval_exp(exp)?;
+            }
+            Statement::Annotations(annotations) => {
+                for annotation in annotations {
+                    match annotation {
+                        Annotation::Assert(exp) => self.check(exp)?,
+                        _ => unreachable!("checked by the type_check analyzer"),
+                    }
+                }
+            }
+        }
+
+        Ok(Flow::Next)
+    }
+
+    fn run_decl(&mut self, decl: &Declaration<'src>) -> Result<(), Exception> {
+        match decl {
+            Declaration::Ident { .. } => Ok(()),
+            Declaration::IdentExp { ident, exp, .. } => {
+                let value = self.eval_exp(exp)?;
+                self.variables().insert(ident.0, value);
+                Ok(())
+            }
+        }
+    }
+
+    fn run_simp(&mut self, simp: &SimpleInstruction<'src>) -> Result<(), Exception> {
+        let SimpleInstruction { lvalue, asnop, exp } = simp;
+
+        let ident = lvalue_ident(lvalue);
+        let right = self.eval_exp(exp)?;
+
+        let value = match asnop_operation(asnop) {
+            None => right,
+            Some(op) => {
+                let left = self
+                    .variables()
+                    .get(ident.0)
+                    .copied()
+                    .expect("checked by the variable_dec_init_and_usage analyzer");
+                binop(&op, &left, &right)?
+            }
+        };
+
+        self.variables().insert(ident.0, value);
+        Ok(())
+    }
+
+    fn eval_exp(&mut self, exp: &Expression<'src>) -> Result<Value, Exception> {
+        match exp {
+            Expression::NestedExp(nested_exp) => self.eval_exp(nested_exp),
+            Expression::Intconst(intconst) => Ok(Value::Int(intconst.value())),
+            Expression::Boolconst(boolconst) => Ok(Value::Bool(*boolconst)),
+            Expression::Charconst(charconst) => Ok(Value::Char(charconst.value())),
+            Expression::Stringconst(stringconst) => {
+                Ok(Value::String(self.heap.alloc(stringconst.value())))
+            }
+            Expression::Ident(ident) => Ok(self.variables()[ident.0]),
+            Expression::Call { ident, args } => Ok(self
+                .eval_call(ident, args)?
+                .expect("checked by the type_check analyzer")),
+            Expression::Binop { left, op, right } => {
+                let left = self.eval_exp(left)?;
+                let right = self.eval_exp(right)?;
+                binop(op, &left, &right)
+            }
+            Expression::Unop { op, right } => {
+                let right = self.eval_exp(right)?.int();
+                match op {
+                    UnOperation::Minus => Ok(Value::Int(right.wrapping_neg())),
+                }
+            }
+            Expression::Read => self.read().map(Value::Int),
+            Expression::Result => Ok(self
+                .results
+                .last()
+                .copied()
+                .flatten()
+                .expect("checked by the type_check analyzer")),
+            Expression::Length(_) => unreachable!("checked by the type_check analyzer"),
+        }
+    }
+
+    fn print(&mut self, value: &Value) -> std::io::Result<()> {
+        match value {
+            Value::Int(int) => writeln!(self.output, "{}", int)?,
+            Value::Bool(bool) => writeln!(self.output, "{}", bool)?,
+            Value::String(string) => {
+                self.output.write_all(self.heap.get(*string))?;
+                writeln!(self.output)?;
+            }
+            Value::Char(_) => unreachable!("checked by the type_check analyzer"),
+        }
+
+        // the process might get killed by a signal afterwards
+        self.output.flush()
+    }
+
+    /// Reads the next whitespace separated int, aborts if there's none.
+    fn read(&mut self) -> Result<i32, Exception> {
+        let mut token = Vec::new();
+
+        loop {
+            let buffer = self.input.fill_buf().map_err(|_| Exception::Abort)?;
+            if buffer.is_empty() {
+                break;
+            }
+
+            let skipped = match token.is_empty() {
+                true => buffer.iter().take_while(|c| is_whitespace(c)).count(),
+                false => 0,
+            };
+            let length = buffer[skipped..]
+                .iter()
+                .take_while(|c| !is_whitespace(c))
+                .count();
+
+            token.extend_from_slice(&buffer[skipped..skipped + length]);
+            let end_of_token = skipped + length < buffer.len();
+            self.input.consume(skipped + length);
+
+            if end_of_token && !token.is_empty() {
+                break;
+            }
+        }
+
+        std::str::from_utf8(&token)
+            .ok()
+            .and_then(|token| token.parse().ok())
+            .ok_or(Exception::Abort)
+    }
+
+    fn eval_call(
+        &mut self,
+        ident: &Identifier<'src>,
+        args: &[Expression<'src>],
+    ) -> Result<Option<Value>, Exception> {
+        // the arguments have to stay reachable while the other arguments are evaluated
+        let base = self.temporaries.len();
+        for arg in args {
+            let value = self.eval_exp(arg)?;
+            self.temporaries.push(value);
+        }
+        let values = self.temporaries.split_off(base);
+
+        match Builtin::from_name(ident.0) {
+            Some(builtin) => library::call(builtin, &values, &mut self.heap).map(Some),
+            None => self.call(ident.0, values),
+        }
+    }
+}
+
+/// The whitespace of C's `isspace`, which `read` skips like the native runtime. Unlike
+/// [u8::is_ascii_whitespace] it contains `\v`.
+fn is_whitespace(c: &u8) -> bool {
+    matches!(c, b' ' | b'\t' | b'\n' | 0x0b | 0x0c | b'\r')
+}
+
+fn lvalue_ident<'a, 'src>(lvalue: &'a LValue<'src>) -> &'a Identifier<'src> {
+    match lvalue {
+        LValue::Ident(ident) => ident,
+        LValue::LValue(lvalue) => lvalue_ident(lvalue),
+    }
+}
+
+fn asnop_operation(asnop: &AsNop) -> Option<BinOperation> {
+    match asnop {
+        AsNop::Equal => None,
+        AsNop::PlusEqual => Some(BinOperation::Plus),
+        AsNop::MinusEqual => Some(BinOperation::Minus),
+        AsNop::MultEqual => Some(BinOperation::Multiplication),
+        AsNop::DivEqual => Some(BinOperation::Division),
+        AsNop::ModEqual => Some(BinOperation::Mod),
+    }
+}
+
+fn binop(op: &BinOperation, left: &Value, right: &Value) -> Result<Value, Exception> {
+    let value = match op {
+        BinOperation::Plus => Value::Int(left.int().wrapping_add(right.int())),
+        BinOperation::Minus => Value::Int(left.int().wrapping_sub(right.int())),
+        BinOperation::Multiplication => Value::Int(left.int().wrapping_mul(right.int())),
+        BinOperation::Division => Value::Int(
+            left.int()
+                .checked_div(right.int())
+                .ok_or(Exception::Arithmetic)?,
+        ),
+        BinOperation::Mod => Value::Int(
+            left.int()
+                .checked_rem(right.int())
+                .ok_or(Exception::Arithmetic)?,
+        ),
+        BinOperation::Equal => Value::Bool(left == right),
+        BinOperation::NotEqual => Value::Bool(left != right),
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(code: &str) -> Result<i32, Exception> {
+        run_with(code, false)
+    }
+
+    fn run_with(code: &str, dynamic_checks: bool) -> Result<i32, Exception> {
+        let options = Options {
+            dynamic_checks,
+            ..Options::default()
+        };
+
+        run_with_io(code, options, "").0
+    }
+
+    fn run_with_io(code: &str, options: Options, input: &str) -> (Result<i32, Exception>, String) {
+        let program = crate::parse(code).unwrap();
+        assert!(crate::analyzer::analyze(&program).is_ok());
+
+        let mut output = Vec::new();
+        let result = interpret_with_io(&program, options, &mut input.as_bytes(), &mut output);
+        (result, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(
+            run("int main() { int x = 3; x *= 4; return x - 2; }"),
+            Ok(10)
+        );
+    }
+
+    #[test]
+    fn wrapping() {
+        assert_eq!(run("int main() { return 2147483647 + 1; }"), Ok(i32::MIN));
+    }
+
+    #[test]
+    fn division_by_zero() {
+        assert_eq!(
+            run("int main() { int x = 0; return 1 / x; }"),
+            Err(Exception::Arithmetic)
+        );
+    }
+
+    #[test]
+    fn division_overflow() {
+        assert_eq!(
+            run("int main() { return -2147483648 % -1; }"),
+            Err(Exception::Arithmetic)
+        );
+    }
+
+    #[test]
+    fn assert_holds() {
+        assert_eq!(run("int main() { assert(1 + 1 == 2); return 1; }"), Ok(1));
+    }
+
+    #[test]
+    fn assert_fails() {
+        assert_eq!(
+            run("int main() { int x = 1; assert(x != 1); return 1; }"),
+            Err(Exception::Abort)
+        );
+    }
+
+    #[test]
+    fn annotations_ignored() {
+        let code = "int main() //@ensures \\result == 1;\n { //@assert false;\n return 0; }";
+        assert_eq!(run_with(code, false), Ok(0));
+    }
+
+    #[test]
+    fn annotation_assert_checked() {
+        let code = "int main() { int x = 2; //@assert x == 3;\n return 0; }";
+        assert_eq!(run_with(code, true), Err(Exception::Abort));
+    }
+
+    #[test]
+    fn ensures_checked() {
+        let code = "int main() //@ensures \\result == 1;\n { return 0; }";
+        assert_eq!(run_with(code, true), Err(Exception::Abort));
+    }
+
+    #[test]
+    fn contracts_hold() {
+        let code = "int main() //@requires true; ensures \\result == 0;\n { return 0; }";
+        assert_eq!(run_with(code, true), Ok(0));
+    }
+
+    #[test]
+    fn nested_calls() {
+        assert_eq!(
+            run("int twice(int x) { return 2 * x; } int main() { return twice(twice(3)); }"),
+            Ok(12)
+        );
+    }
+
+    #[test]
+    fn void_function() {
+        assert_eq!(
+            run("void check(int x) { assert(x == 1); return; } int main() { check(1); return 0; }"),
+            Ok(0)
+        );
+    }
+
+    #[test]
+    fn void_function_aborts() {
+        assert_eq!(
+            run("void check(int x) { assert(x == 1); } int main() { check(2); return 0; }"),
+            Err(Exception::Abort)
+        );
+    }
+
+    #[test]
+    fn requires_of_callee() {
+        let code =
+            "int f(int x) //@requires x != 0;\n { return 1 / x; } int main() { return f(0); }";
+        assert_eq!(run_with(code, true), Err(Exception::Abort));
+        assert_eq!(run_with(code, false), Err(Exception::Arithmetic));
+    }
+
+    #[test]
+    fn print_values() {
+        let code =
+            r#"int main() { print(-3); print(1 == 1); print(string_join("a", "b")); return 0; }"#;
+        assert_eq!(
+            run_with_io(code, Options::default(), ""),
+            (Ok(0), "-3\ntrue\nab\n".to_string())
+        );
+    }
+
+    #[test]
+    fn read_ints() {
+        let code = "int main() { int x = read(); return x - read(); }";
+        assert_eq!(
+            run_with_io(code, Options::default(), "  10\n\t-5 ").0,
+            Ok(15)
+        );
+        // `\v` is whitespace in C as well
+        assert_eq!(
+            run_with_io(code, Options::default(), "\x0b10\x0b5\x0b").0,
+            Ok(5)
+        );
+    }
+
+    #[test]
+    fn read_eof() {
+        let code = "int main() { return read(); }";
+        assert_eq!(
+            run_with_io(code, Options::default(), " \n").0,
+            Err(Exception::Abort)
+        );
+    }
+
+    #[test]
+    fn read_invalid() {
+        let code = "int main() { return read(); }";
+        assert_eq!(
+            run_with_io(code, Options::default(), "abc").0,
+            Err(Exception::Abort)
+        );
+    }
+
+    /// Doubles a string often enough to trigger some collections.
+    fn run_doubling(garbage_collection: bool) -> (Result<i32, Exception>, usize) {
+        let doublings = "s = string_join(s, s);".repeat(18);
+        let code = format!(
+            r#"int main() {{ string keep = "keep"; string s = "ab"; {} return string_length(s) + string_length(keep); }}"#,
+            doublings
+        );
+        let program = crate::parse(&code).unwrap();
+        assert!(crate::analyzer::analyze(&program).is_ok());
+
+        let options = Options {
+            garbage_collection,
+            ..Options::default()
+        };
+        let (mut input, mut output) = (std::io::empty(), std::io::sink());
+        let mut interpreter = Interpreter::new(&program, options, &mut input, &mut output);
+        let result = interpreter.run();
+
+        (result, interpreter.heap.len())
+    }
+
+    #[test]
+    fn garbage_collection() {
+        let (result, objects) = run_doubling(true);
+
+        assert_eq!(result, Ok((2 << 18) + 4));
+        assert!(objects < 10);
+    }
+
+    #[test]
+    fn garbage_collection_disabled() {
+        let (result, objects) = run_doubling(false);
+
+        assert_eq!(result, Ok((2 << 18) + 4));
+        assert_eq!(objects, 20);
+    }
+
+    #[test]
+    fn results_are_roots() {
+        // `h` collects garbage while the `\result` of `f` is still needed
+        let code = format!(
+            r#"int h() {{ string s = "ab"; {} return 1; }}
+string g() //@ensures h() == 1;
+{{ return "y"; }}
+string f() //@ensures string_equal(g(), \result) == false;
+{{ return string_join("x", "z"); }}
+int main() {{ print(f()); return 0; }}"#,
+            "s = string_join(s, s);".repeat(17)
+        );
+        let options = Options {
+            dynamic_checks: true,
+            ..Options::default()
+        };
+
+        let (result, output) = run_with_io(&code, options, "");
+        assert_eq!(result, Ok(0));
+        assert!(output.starts_with("xz"));
+    }
+
+    #[test]
+    fn string_length_with_escapes() {
+        assert_eq!(
+            run(r#"int main() { string s = "a\tb\n"; return string_length(s); }"#),
+            Ok(4)
+        );
+    }
+
+    #[test]
+    fn char_escape() {
+        assert_eq!(run(r"int main() { return char_ord('\n'); }"), Ok(10));
+    }
+
+    #[test]
+    fn string_join_and_compare() {
+        assert_eq!(
+            run(
+                r#"int main() { string s = string_join("ab", "c"); return string_compare(s, "abd"); }"#
+            ),
+            Ok(-1)
+        );
+    }
+
+    #[test]
+    fn string_charat_out_of_bounds() {
+        assert_eq!(
+            run(r#"int main() { char c = string_charat("abc", 3); return 0; }"#),
+            Err(Exception::Abort)
+        );
+    }
+}
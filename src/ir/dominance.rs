@@ -0,0 +1,248 @@
+//! The dominator tree and the dominance frontiers of the control flow graph of a function.
+//!
+//! A block dominates another one if every path from the entry to the other block goes through it.
+//! The immediate dominators are computed with the iterative algorithm of Cooper, Harvey and
+//! Kennedy ("A Simple, Fast Dominance Algorithm").
+
+use super::*;
+
+#[derive(Debug, Clone)]
+pub struct Dominators {
+    /// The immediate dominator of every block, `None` for the entry and unreachable blocks.
+    idoms: Vec<Option<BlockId>>,
+    /// The reachable blocks in reverse postorder, starting with the entry.
+    order: Vec<BlockId>,
+}
+
+impl Dominators {
+    pub fn new(function: &Function) -> Self {
+        let order = reverse_postorder(function);
+        // the position of every block in the order, the entry has the smallest
+        let mut positions = vec![usize::MAX; function.blocks.len()];
+        for (position, block) in order.iter().enumerate() {
+            positions[block.0] = position;
+        }
+        let predecessors = function.predecessors();
+
+        // the entry is its own dominator while the algorithm runs
+        let mut idoms = vec![None; function.blocks.len()];
+        idoms[0] = Some(BlockId(0));
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in &order[1..] {
+                let mut idom = None;
+                for &predecessor in &predecessors[block.0] {
+                    if idoms[predecessor.0].is_none() {
+                        continue;
+                    }
+                    idom = Some(match idom {
+                        None => predecessor,
+                        Some(idom) => intersect(&idoms, &positions, predecessor, idom),
+                    });
+                }
+
+                if idoms[block.0] != idom {
+                    idoms[block.0] = idom;
+                    changed = true;
+                }
+            }
+        }
+
+        idoms[0] = None;
+        Self { idoms, order }
+    }
+
+    /// The closest block which dominates the block except itself.
+    pub fn idom(&self, block: BlockId) -> Option<BlockId> {
+        self.idoms[block.0]
+    }
+
+    pub fn is_reachable(&self, block: BlockId) -> bool {
+        block.0 == 0 || self.idoms[block.0].is_some()
+    }
+
+    /// Whether every path from the entry to `block` goes through `dominator`, a block dominates
+    /// itself.
+    pub fn dominates(&self, dominator: BlockId, block: BlockId) -> bool {
+        let mut current = Some(block);
+        while let Some(block) = current {
+            if block == dominator {
+                return true;
+            }
+            current = self.idom(block);
+        }
+
+        false
+    }
+
+    /// The reachable blocks in reverse postorder, so a block comes before the blocks it dominates.
+    pub fn order(&self) -> &[BlockId] {
+        &self.order
+    }
+
+    /// The blocks every block immediately dominates, which are its children in the dominator tree.
+    pub fn children(&self) -> Vec<Vec<BlockId>> {
+        let mut children = vec![Vec::new(); self.idoms.len()];
+        for &block in &self.order {
+            if let Some(idom) = self.idom(block) {
+                children[idom.0].push(block);
+            }
+        }
+
+        children
+    }
+
+    /// The blocks where the dominance of every block ends: the block dominates a predecessor of
+    /// them but doesn't strictly dominate them. The edge into the function isn't counted, so the
+    /// entry mustn't have predecessors.
+    pub fn frontiers(&self, function: &Function) -> Vec<Vec<BlockId>> {
+        let mut frontiers = vec![Vec::new(); function.blocks.len()];
+
+        for (block, predecessors) in function.predecessors().into_iter().enumerate() {
+            let block = BlockId(block);
+            if predecessors.len() < 2 || !self.is_reachable(block) {
+                continue;
+            }
+
+            for predecessor in predecessors {
+                if !self.is_reachable(predecessor) {
+                    continue;
+                }
+                // every block from the predecessor up to the immediate dominator of the block
+                let mut runner = Some(predecessor);
+                while let Some(current) = runner
+                    && runner != self.idom(block)
+                {
+                    if !frontiers[current.0].contains(&block) {
+                        frontiers[current.0].push(block);
+                    }
+                    runner = self.idom(current);
+                }
+            }
+        }
+
+        frontiers
+    }
+}
+
+/// The closest common dominator of two blocks.
+fn intersect(
+    idoms: &[Option<BlockId>],
+    positions: &[usize],
+    mut left: BlockId,
+    mut right: BlockId,
+) -> BlockId {
+    while left != right {
+        while positions[left.0] > positions[right.0] {
+            left = idoms[left.0].expect("the dominators of processed blocks are known");
+        }
+        while positions[right.0] > positions[left.0] {
+            right = idoms[right.0].expect("the dominators of processed blocks are known");
+        }
+    }
+
+    left
+}
+
+fn reverse_postorder(function: &Function) -> Vec<BlockId> {
+    let mut visited = vec![false; function.blocks.len()];
+    let mut postorder = Vec::with_capacity(function.blocks.len());
+    // the blocks on the current path with their successors which haven't been visited yet
+    let mut stack = vec![(BlockId(0), function.blocks[0].terminator.successors())];
+    visited[0] = true;
+
+    while let Some((block, successors)) = stack.last_mut() {
+        match successors.pop() {
+            Some(successor) if !visited[successor.0] => {
+                visited[successor.0] = true;
+                let successors = function.blocks[successor.0].terminator.successors();
+                stack.push((successor, successors));
+            }
+            Some(_) => {}
+            None => {
+                postorder.push(*block);
+                stack.pop();
+            }
+        }
+    }
+
+    postorder.reverse();
+    postorder
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn function(terminators: Vec<Terminator>) -> Function<'static> {
+        let blocks = terminators
+            .into_iter()
+            .map(|terminator| Block {
+                instructions: vec![],
+                terminator,
+            })
+            .collect();
+
+        Function {
+            name: "f",
+            params: vec![Var(0)],
+            blocks,
+            var_count: 1,
+        }
+    }
+
+    fn branch(then: usize, otherwise: usize) -> Terminator {
+        Terminator::Branch {
+            cond: Operand::Var(Var(0)),
+            then: BlockId(then),
+            otherwise: BlockId(otherwise),
+        }
+    }
+
+    #[test]
+    fn diamond_with_loop() {
+        // b0 -> b1 | b2 -> b3 -> b4 -> b3 | b5, b6 can't be reached
+        let function = function(vec![
+            branch(1, 2),
+            Terminator::Jump(BlockId(3)),
+            Terminator::Jump(BlockId(3)),
+            Terminator::Jump(BlockId(4)),
+            branch(3, 5),
+            Terminator::Return(None),
+            Terminator::Jump(BlockId(5)),
+        ]);
+        let dominators = Dominators::new(&function);
+
+        let idoms: Vec<_> = (0..7)
+            .map(|block| dominators.idom(BlockId(block)))
+            .collect();
+        assert_eq!(
+            idoms,
+            [
+                None,
+                Some(BlockId(0)),
+                Some(BlockId(0)),
+                Some(BlockId(0)),
+                Some(BlockId(3)),
+                Some(BlockId(4)),
+                None
+            ]
+        );
+        assert!(dominators.dominates(BlockId(3), BlockId(5)));
+        assert!(dominators.dominates(BlockId(4), BlockId(4)));
+        assert!(!dominators.dominates(BlockId(1), BlockId(3)));
+        assert!(!dominators.is_reachable(BlockId(6)));
+        assert_eq!(dominators.order()[0], BlockId(0));
+        assert_eq!(dominators.children()[3], [BlockId(4)]);
+
+        let frontiers = dominators.frontiers(&function);
+        assert_eq!(frontiers[0], []);
+        assert_eq!(frontiers[1], [BlockId(3)]);
+        assert_eq!(frontiers[2], [BlockId(3)]);
+        assert_eq!(frontiers[3], [BlockId(3)]);
+        assert_eq!(frontiers[4], [BlockId(3)]);
+        assert_eq!(frontiers[5], []);
+    }
+}
@@ -0,0 +1,461 @@
+//! Lowers the type checked AST into the IR.
+
+use std::collections::HashMap;
+
+use super::*;
+use crate::grammar::{
+    self, Annotation, AsNop, BinOperation, Declaration, Expression, LValue, SimpleInstruction,
+    Statement, Type, UnOperation,
+};
+
+/// Lowers the program which has to pass the analyzers beforehand.
+/// The annotations are only turned into runtime checks if `dynamic_checks` is set, like in the
+/// interpreter.
+pub fn lower<'src>(program: &grammar::Program<'src>, dynamic_checks: bool) -> Program<'src> {
+    let return_types = program
+        .0
+        .iter()
+        .map(|function| (function.ident.0, function.return_type))
+        .collect();
+
+    let functions = program
+        .0
+        .iter()
+        .map(|function| Lowering::new(&return_types, dynamic_checks, function).lower(function))
+        .collect();
+
+    Program { functions }
+}
+
+struct Lowering<'a, 'src> {
+    return_types: &'a HashMap<&'src str, Type>,
+    dynamic_checks: bool,
+    function: Function<'src>,
+    return_type: Type,
+    /// The block new instructions are appended to.
+    current: BlockId,
+    /// The block every failed check jumps to, created on demand.
+    abort: Option<BlockId>,
+    variables: HashMap<&'src str, (Var, Type)>,
+    /// The `ensures` contracts which are checked before every return.
+    ensures: Vec<&'a Expression<'src>>,
+    /// The value of `\result`, only set while lowering an `ensures` contract.
+    result: Option<Operand>,
+}
+
+impl<'a, 'src> Lowering<'a, 'src> {
+    fn new(
+        return_types: &'a HashMap<&'src str, Type>,
+        dynamic_checks: bool,
+        function: &'a grammar::Function<'src>,
+    ) -> Self {
+        let entry = Block {
+            instructions: Vec::new(),
+            terminator: Terminator::Unreachable,
+        };
+
+        Self {
+            return_types,
+            dynamic_checks,
+            function: Function {
+                name: function.ident.0,
+                params: Vec::new(),
+                blocks: vec![entry],
+                var_count: 0,
+            },
+            return_type: function.return_type,
+            current: BlockId(0),
+            abort: None,
+            variables: HashMap::new(),
+            ensures: Vec::new(),
+            result: None,
+        }
+    }
+
+    fn lower(mut self, function: &'a grammar::Function<'src>) -> Function<'src> {
+        for param in &function.params {
+            let var = self.declare(param.ident.0, param.ty);
+            self.function.params.push(var);
+        }
+
+        if self.dynamic_checks {
+            for annotation in &function.annotations {
+                match annotation {
+                    Annotation::Requires(exp) => self.check(exp),
+                    Annotation::Ensures(exp) => self.ensures.push(exp),
+                    Annotation::Assert(_) => unreachable!("checked by the type_check analyzer"),
+                    Annotation::LoopInvariant(_) => {
+                        unreachable!("checked by the unsupported_features analyzer")
+                    }
+                }
+            }
+        }
+
+        for statement in &function.statements {
+            self.lower_statement(statement);
+        }
+
+        // a function with a return value can't run off its end
+        if self.return_type == Type::Void {
+            self.ret(None);
+        }
+
+        self.function
+    }
+
+    fn declare(&mut self, ident: &'src str, ty: Type) -> Var {
+        let var = self.function.new_var();
+        self.variables.insert(ident, (var, ty));
+        var
+    }
+
+    fn new_block(&mut self) -> BlockId {
+        self.function.blocks.push(Block {
+            instructions: Vec::new(),
+            terminator: Terminator::Unreachable,
+        });
+
+        BlockId(self.function.blocks.len() - 1)
+    }
+
+    fn push(&mut self, instruction: Instruction<'src>) {
+        self.function.blocks[self.current.0]
+            .instructions
+            .push(instruction);
+    }
+
+    /// Ends the current block and continues in a new one, which is unreachable unless the
+    /// terminator jumps to it.
+    fn terminate(&mut self, terminator: Terminator) -> BlockId {
+        self.function.blocks[self.current.0].terminator = terminator;
+        self.current = self.new_block();
+        self.current
+    }
+
+    /// Aborts if the boolean expression is false.
+    fn check(&mut self, exp: &Expression<'src>) {
+        let cond = self.lower_exp(exp);
+        let abort = match self.abort {
+            Some(abort) => abort,
+            None => {
+                let abort = self.new_block();
+                self.function.blocks[abort.0].terminator = Terminator::Abort;
+                self.abort = Some(abort);
+                abort
+            }
+        };
+
+        let then = BlockId(self.function.blocks.len());
+        self.terminate(Terminator::Branch {
+            cond,
+            then,
+            otherwise: abort,
+        });
+    }
+
+    fn ret(&mut self, value: Option<Operand>) {
+        self.result = value;
+        for exp in self.ensures.clone() {
+            self.check(exp);
+        }
+        self.result = None;
+
+        self.terminate(Terminator::Return(value));
+    }
+
+    fn lower_statement(&mut self, statement: &Statement<'src>) {
+        match statement {
+            Statement::Decl(Declaration::Ident { ty, ident }) => {
+                self.declare(ident.0, *ty);
+            }
+            Statement::Decl(Declaration::IdentExp { ty, ident, exp }) => {
+                let src = self.lower_exp(exp);
+                let dest = self.declare(ident.0, *ty);
+                self.push(Instruction::Copy { dest, src });
+            }
+            Statement::Simp(simp) => self.lower_simp(simp),
+            Statement::Return(exp) => {
+                let value = exp.as_ref().map(|exp| self.lower_exp(exp));
+                self.ret(value);
+            }
+            Statement::Assert(exp) => self.check(exp),
+            Statement::Print(exp) => {
+                let kind = match self.type_of(exp) {
+                    Type::Int => PrintKind::Int,
+                    Type::Bool => PrintKind::Bool,
+                    Type::String => PrintKind::String,
+                    Type::Char | Type::Void => unreachable!("checked by the type_check analyzer"),
+                };
+                let value = self.lower_exp(exp);
+                self.push(Instruction::Print { kind, value });
+            }
+            Statement::Exp(Expression::Call { ident, args }) => {
+                self.lower_call(ident.0, args, false);
+            }
+            Statement::Exp(exp) => {
+                self.lower_exp(exp);
+            }
+            Statement::Annotations(annotations) => {
+                for annotation in annotations {
+                    match annotation {
+                        Annotation::Assert(exp) if self.dynamic_checks => self.check(exp),
+                        Annotation::Assert(_) => {}
+                        Annotation::Requires(_) | Annotation::Ensures(_) => {
+                            unreachable!("checked by the type_check analyzer")
+                        }
+                        Annotation::LoopInvariant(_) => {
+                            unreachable!("checked by the unsupported_features analyzer")
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn lower_simp(&mut self, simp: &SimpleInstruction<'src>) {
+        let SimpleInstruction { lvalue, asnop, exp } = simp;
+
+        let mut lvalue = lvalue;
+        while let LValue::LValue(inner) = lvalue {
+            lvalue = inner;
+        }
+        let LValue::Ident(ident) = lvalue else {
+            unreachable!()
+        };
+        let dest = self.variables[ident.0].0;
+
+        let src = self.lower_exp(exp);
+        let op = match asnop {
+            AsNop::Equal => {
+                self.push(Instruction::Copy { dest, src });
+                return;
+            }
+            AsNop::PlusEqual => BinOp::Add,
+            AsNop::MinusEqual => BinOp::Sub,
+            AsNop::MultEqual => BinOp::Mul,
+            AsNop::DivEqual => BinOp::Div,
+            AsNop::ModEqual => BinOp::Mod,
+        };
+
+        self.push(Instruction::Binary {
+            dest,
+            op,
+            left: Operand::Var(dest),
+            right: src,
+        });
+    }
+
+    fn lower_call(
+        &mut self,
+        ident: &'src str,
+        args: &[Expression<'src>],
+        used: bool,
+    ) -> Option<Var> {
+        let args = args.iter().map(|arg| self.lower_exp(arg)).collect();
+        let dest = used.then(|| self.function.new_var());
+        let callee = match Builtin::from_name(ident) {
+            Some(builtin) => Callee::Builtin(builtin),
+            None => Callee::Function(ident),
+        };
+
+        self.push(Instruction::Call { dest, callee, args });
+        dest
+    }
+
+    fn lower_exp(&mut self, exp: &Expression<'src>) -> Operand {
+        match exp {
+            Expression::NestedExp(exp) => self.lower_exp(exp),
+            Expression::Intconst(intconst) => Operand::Const(intconst.value()),
+            Expression::Boolconst(boolconst) => Operand::Const(*boolconst as i32),
+            Expression::Charconst(charconst) => Operand::Const(charconst.value() as i32),
+            Expression::Stringconst(stringconst) => {
+                let dest = self.function.new_var();
+                self.push(Instruction::String {
+                    dest,
+                    value: stringconst.value(),
+                });
+                Operand::Var(dest)
+            }
+            Expression::Ident(ident) => Operand::Var(self.variables[ident.0].0),
+            Expression::Call { ident, args } => Operand::Var(
+                self.lower_call(ident.0, args, true)
+                    .expect("the value is used"),
+            ),
+            Expression::Binop { left, op, right } => {
+                let left = self.lower_exp(left);
+                let right = self.lower_exp(right);
+                let op = match op {
+                    BinOperation::Plus => BinOp::Add,
+                    BinOperation::Minus => BinOp::Sub,
+                    BinOperation::Multiplication => BinOp::Mul,
+                    BinOperation::Division => BinOp::Div,
+                    BinOperation::Mod => BinOp::Mod,
+                    BinOperation::Equal => BinOp::Eq,
+                    BinOperation::NotEqual => BinOp::Ne,
+                };
+
+                self.binary(op, left, right)
+            }
+            Expression::Unop {
+                op: UnOperation::Minus,
+                right,
+            } => {
+                let right = self.lower_exp(right);
+                self.binary(BinOp::Sub, Operand::Const(0), right)
+            }
+            Expression::Read => {
+                let dest = self.function.new_var();
+                self.push(Instruction::Read { dest });
+                Operand::Var(dest)
+            }
+            Expression::Result => self.result.expect("checked by the type_check analyzer"),
+            Expression::Length(_) => unreachable!("checked by the unsupported_features analyzer"),
+        }
+    }
+
+    fn binary(&mut self, op: BinOp, left: Operand, right: Operand) -> Operand {
+        let dest = self.function.new_var();
+        self.push(Instruction::Binary {
+            dest,
+            op,
+            left,
+            right,
+        });
+        Operand::Var(dest)
+    }
+
+    fn type_of(&self, exp: &Expression<'src>) -> Type {
+        match exp {
+            Expression::NestedExp(exp) => self.type_of(exp),
+            Expression::Intconst(_) | Expression::Unop { .. } | Expression::Read => Type::Int,
+            Expression::Boolconst(_) => Type::Bool,
+            Expression::Charconst(_) => Type::Char,
+            Expression::Stringconst(_) => Type::String,
+            Expression::Ident(ident) => self.variables[ident.0].1,
+            Expression::Call { ident, .. } => match Builtin::from_name(ident.0) {
+                Some(builtin) => builtin.signature().1,
+                None => self.return_types[ident.0],
+            },
+            Expression::Binop { op, .. } => match op {
+                BinOperation::Equal | BinOperation::NotEqual => Type::Bool,
+                _ => Type::Int,
+            },
+            Expression::Result => self.return_type,
+            Expression::Length(_) => unreachable!("checked by the unsupported_features analyzer"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lower_code(code: &str, dynamic_checks: bool) -> String {
+        let program = crate::parse(code).unwrap();
+        assert!(crate::analyzer::analyze(&program).is_ok());
+
+        lower(&program, dynamic_checks).to_string()
+    }
+
+    #[test]
+    fn arithmetic() {
+        assert_eq!(
+            lower_code("int main() { int x = 1; x += -x * 2; return x; }", false),
+            "function main() {
+b0:
+    %0 = 1
+    %1 = sub 0, %0
+    %2 = mul %1, 2
+    %0 = add %0, %2
+    return %0
+b1:
+    unreachable
+}
+"
+        );
+    }
+
+    #[test]
+    fn calls_and_print() {
+        assert_eq!(
+            lower_code(
+                r#"void f(string s) { print(s); }
+                int main() { f(string_join("a", "b")); print(true); return read(); }"#,
+                false
+            ),
+            r#"function f(%0) {
+b0:
+    print string %0
+    return
+b1:
+    unreachable
+}
+
+function main() {
+b0:
+    %0 = string "a"
+    %1 = string "b"
+    %2 = call string_join(%0, %1)
+    call f(%2)
+    print bool 1
+    %3 = read
+    return %3
+b1:
+    unreachable
+}
+"#
+        );
+    }
+
+    #[test]
+    fn assert_branches_to_abort() {
+        assert_eq!(
+            lower_code("int main() { assert(1 == 2); return 0; }", false),
+            "function main() {
+b0:
+    %0 = eq 1, 2
+    branch %0, b2, b1
+b1:
+    abort
+b2:
+    return 0
+b3:
+    unreachable
+}
+"
+        );
+    }
+
+    #[test]
+    fn contracts_only_with_dynamic_checks() {
+        let code = "int main() //@ensures \\result == 0;\n { //@assert true;\n return 0; }";
+
+        assert_eq!(
+            lower_code(code, false),
+            "function main() {
+b0:
+    return 0
+b1:
+    unreachable
+}
+"
+        );
+        assert_eq!(
+            lower_code(code, true),
+            "function main() {
+b0:
+    branch 1, b2, b1
+b1:
+    abort
+b2:
+    %0 = eq 0, 0
+    branch %0, b3, b1
+b3:
+    return 0
+b4:
+    unreachable
+}
+"
+        );
+    }
+}
@@ -0,0 +1,416 @@
+//! A three-address intermediate representation with an explicit control flow graph.
+//!
+//! Every value is a 32 bit int or a string. Bools are `0` and `1`, chars their ASCII code.
+//! Variables may be assigned multiple times until the program is converted into SSA form.
+pub mod dominance;
+pub mod lower;
+pub mod ssa;
+
+use std::fmt;
+
+use crate::library::Builtin;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program<'src> {
+    pub functions: Vec<Function<'src>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function<'src> {
+    pub name: &'src str,
+    pub params: Vec<Var>,
+    /// The first block is the entry of the function.
+    pub blocks: Vec<Block<'src>>,
+    /// The number of variables, so every variable is smaller than this.
+    pub var_count: u32,
+}
+
+/// A variable local to its function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Var(pub u32);
+
+/// The index of a block in [`Function::blocks`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub usize);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block<'src> {
+    pub instructions: Vec<Instruction<'src>>,
+    pub terminator: Terminator,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Operand {
+    Const(i32),
+    Var(Var),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction<'src> {
+    Copy {
+        dest: Var,
+        src: Operand,
+    },
+    Binary {
+        dest: Var,
+        op: BinOp,
+        left: Operand,
+        right: Operand,
+    },
+    /// A string literal, which lives as long as the program.
+    String {
+        dest: Var,
+        value: Vec<u8>,
+    },
+    Call {
+        /// `None` if the function returns `void` or the value is thrown away.
+        dest: Option<Var>,
+        callee: Callee<'src>,
+        args: Vec<Operand>,
+    },
+    Print {
+        kind: PrintKind,
+        value: Operand,
+    },
+    Read {
+        dest: Var,
+    },
+    /// Only exists in SSA form, at the start of a block: the operand of the predecessor the
+    /// control came from.
+    Phi {
+        dest: Var,
+        args: Vec<(BlockId, Operand)>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    /// Traps on a zero divisor and on `i32::MIN / -1`.
+    Div,
+    /// Traps like [`BinOp::Div`].
+    Mod,
+    Eq,
+    Ne,
+    /// Shifts the left operand to the left, the count is taken modulo 32 like on x86.
+    Shl,
+    /// Shifts the left operand to the right and keeps its sign.
+    Sar,
+    /// Shifts the left operand to the right and fills in zeros.
+    Shr,
+    /// The upper 32 bits of the 64 bit product.
+    MulHigh,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Callee<'src> {
+    Function(&'src str),
+    Builtin(Builtin),
+}
+
+/// How the value of a `print` is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrintKind {
+    Int,
+    Bool,
+    String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Return(Option<Operand>),
+    Jump(BlockId),
+    /// Jumps to `then` if `cond` isn't zero.
+    Branch {
+        cond: Operand,
+        then: BlockId,
+        otherwise: BlockId,
+    },
+    /// A failed runtime check which raises `SIGABRT`.
+    Abort,
+    /// The end of a block which can't be reached, like the code after a `return`.
+    Unreachable,
+}
+
+impl BinOp {
+    fn name(&self) -> &'static str {
+        match self {
+            BinOp::Add => "add",
+            BinOp::Sub => "sub",
+            BinOp::Mul => "mul",
+            BinOp::Div => "div",
+            BinOp::Mod => "mod",
+            BinOp::Eq => "eq",
+            BinOp::Ne => "ne",
+            BinOp::Shl => "shl",
+            BinOp::Sar => "sar",
+            BinOp::Shr => "shr",
+            BinOp::MulHigh => "mulhi",
+        }
+    }
+
+    /// Whether the operands can be swapped without changing the result.
+    pub fn is_commutative(&self) -> bool {
+        match self {
+            BinOp::Add | BinOp::Mul | BinOp::Eq | BinOp::Ne | BinOp::MulHigh => true,
+            BinOp::Sub | BinOp::Div | BinOp::Mod | BinOp::Shl | BinOp::Sar | BinOp::Shr => false,
+        }
+    }
+
+    /// Computes the operation like the compiled program with wrapping arithmetic.
+    /// Returns `None` if the operation traps, which has to stay in the program.
+    pub fn eval(&self, left: i32, right: i32) -> Option<i32> {
+        match self {
+            BinOp::Add => Some(left.wrapping_add(right)),
+            BinOp::Sub => Some(left.wrapping_sub(right)),
+            BinOp::Mul => Some(left.wrapping_mul(right)),
+            BinOp::Div => left.checked_div(right),
+            BinOp::Mod => left.checked_rem(right),
+            BinOp::Eq => Some((left == right) as i32),
+            BinOp::Ne => Some((left != right) as i32),
+            BinOp::Shl => Some(left.wrapping_shl(right as u32)),
+            BinOp::Sar => Some(left.wrapping_shr(right as u32)),
+            BinOp::Shr => Some((left as u32).wrapping_shr(right as u32) as i32),
+            BinOp::MulHigh => Some(((left as i64 * right as i64) >> 32) as i32),
+        }
+    }
+}
+
+impl Function<'_> {
+    pub fn new_var(&mut self) -> Var {
+        self.var_count += 1;
+        Var(self.var_count - 1)
+    }
+
+    /// The blocks every block may be reached from directly, once per edge.
+    pub fn predecessors(&self) -> Vec<Vec<BlockId>> {
+        let mut predecessors = vec![Vec::new(); self.blocks.len()];
+        for (id, block) in self.blocks.iter().enumerate() {
+            for successor in block.terminator.successors() {
+                predecessors[successor.0].push(BlockId(id));
+            }
+        }
+
+        predecessors
+    }
+}
+
+impl Instruction<'_> {
+    /// The variable the instruction assigns.
+    pub fn dest(&self) -> Option<Var> {
+        match self {
+            Instruction::Copy { dest, .. }
+            | Instruction::Binary { dest, .. }
+            | Instruction::String { dest, .. }
+            | Instruction::Read { dest }
+            | Instruction::Phi { dest, .. } => Some(*dest),
+            Instruction::Call { dest, .. } => *dest,
+            Instruction::Print { .. } => None,
+        }
+    }
+
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Instruction::Copy { src, .. } => vec![src],
+            Instruction::Binary { left, right, .. } => vec![left, right],
+            Instruction::String { .. } | Instruction::Read { .. } => vec![],
+            Instruction::Call { args, .. } => args.iter().collect(),
+            Instruction::Print { value, .. } => vec![value],
+            Instruction::Phi { args, .. } => args.iter().map(|(_, arg)| arg).collect(),
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Instruction::Copy { src, .. } => vec![src],
+            Instruction::Binary { left, right, .. } => vec![left, right],
+            Instruction::String { .. } | Instruction::Read { .. } => vec![],
+            Instruction::Call { args, .. } => args.iter_mut().collect(),
+            Instruction::Print { value, .. } => vec![value],
+            Instruction::Phi { args, .. } => args.iter_mut().map(|(_, arg)| arg).collect(),
+        }
+    }
+}
+
+impl Terminator {
+    /// Replaces the targets of the terminator.
+    pub fn retarget(&mut self, mut target: impl FnMut(BlockId) -> BlockId) {
+        match self {
+            Terminator::Jump(block) => *block = target(*block),
+            Terminator::Branch {
+                then, otherwise, ..
+            } => {
+                *then = target(*then);
+                *otherwise = target(*otherwise);
+            }
+            Terminator::Return(_) | Terminator::Abort | Terminator::Unreachable => {}
+        }
+    }
+
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Terminator::Return(Some(value)) => vec![value],
+            Terminator::Branch { cond, .. } => vec![cond],
+            Terminator::Return(None)
+            | Terminator::Jump(_)
+            | Terminator::Abort
+            | Terminator::Unreachable => vec![],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Terminator::Return(Some(value)) => vec![value],
+            Terminator::Branch { cond, .. } => vec![cond],
+            Terminator::Return(None)
+            | Terminator::Jump(_)
+            | Terminator::Abort
+            | Terminator::Unreachable => vec![],
+        }
+    }
+
+    /// The blocks the terminator may continue with.
+    pub fn successors(&self) -> Vec<BlockId> {
+        match self {
+            Terminator::Jump(target) => vec![*target],
+            Terminator::Branch {
+                then, otherwise, ..
+            } => vec![*then, *otherwise],
+            Terminator::Return(_) | Terminator::Abort | Terminator::Unreachable => vec![],
+        }
+    }
+}
+
+impl fmt::Display for Program<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, function) in self.functions.iter().enumerate() {
+            if index > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", function)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Function<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "function {}(", self.name)?;
+        write_list(f, &self.params)?;
+        writeln!(f, ") {{")?;
+
+        for (id, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "{}:", BlockId(id))?;
+            for instruction in &block.instructions {
+                writeln!(f, "    {}", instruction)?;
+            }
+            writeln!(f, "    {}", block.terminator)?;
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Var {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b{}", self.0)
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Const(value) => write!(f, "{}", value),
+            Operand::Var(var) => write!(f, "{}", var),
+        }
+    }
+}
+
+impl fmt::Display for Instruction<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Copy { dest, src } => write!(f, "{} = {}", dest, src),
+            Instruction::Binary {
+                dest,
+                op,
+                left,
+                right,
+            } => write!(f, "{} = {} {}, {}", dest, op.name(), left, right),
+            Instruction::String { dest, value } => {
+                write!(
+                    f,
+                    "{} = string {:?}",
+                    dest,
+                    value.escape_ascii().to_string()
+                )
+            }
+            Instruction::Call { dest, callee, args } => {
+                if let Some(dest) = dest {
+                    write!(f, "{} = ", dest)?;
+                }
+                match callee {
+                    Callee::Function(name) => write!(f, "call {}(", name)?,
+                    Callee::Builtin(builtin) => write!(f, "call {}(", builtin.name())?,
+                }
+                write_list(f, args)?;
+                write!(f, ")")
+            }
+            Instruction::Print { kind, value } => {
+                let kind = match kind {
+                    PrintKind::Int => "int",
+                    PrintKind::Bool => "bool",
+                    PrintKind::String => "string",
+                };
+                write!(f, "print {} {}", kind, value)
+            }
+            Instruction::Read { dest } => write!(f, "{} = read", dest),
+            Instruction::Phi { dest, args } => {
+                write!(f, "{} = phi ", dest)?;
+                for (index, (block, arg)) in args.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "[{}: {}]", block, arg)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminator::Return(None) => write!(f, "return"),
+            Terminator::Return(Some(value)) => write!(f, "return {}", value),
+            Terminator::Jump(target) => write!(f, "jump {}", target),
+            Terminator::Branch {
+                cond,
+                then,
+                otherwise,
+            } => write!(f, "branch {}, {}, {}", cond, then, otherwise),
+            Terminator::Abort => write!(f, "abort"),
+            Terminator::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter<'_>, items: &[impl fmt::Display]) -> fmt::Result {
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", item)?;
+    }
+
+    Ok(())
+}
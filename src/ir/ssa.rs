@@ -0,0 +1,490 @@
+//! Converts functions into SSA form, where every variable is assigned exactly once, and back.
+//!
+//! The construction follows Cytron et al.: phis are placed at the iterated dominance frontiers
+//! of the assignments and the variables are renamed in a walk over the dominator tree. Only the
+//! variables which are used in another block than the one assigning them get phis.
+//!
+//! The destruction replaces the phis by copies at the end of the predecessors. The copies of an
+//! edge happen at the same time, so they are sequentialised with a new variable to break cycles.
+//! Edges from a branch into a block with phis are split first, so the copies only run on their
+//! edge.
+
+use std::collections::HashSet;
+
+use super::{dominance::Dominators, *};
+
+pub fn construct(program: &mut Program) {
+    for function in &mut program.functions {
+        into_ssa(function);
+    }
+}
+
+pub fn destruct(program: &mut Program) {
+    for function in &mut program.functions {
+        out_of_ssa(function);
+    }
+}
+
+fn into_ssa(function: &mut Function) {
+    // the entry can't get phis, as there's no block for the edge into the function
+    if !function.predecessors()[0].is_empty() {
+        move_entry(function);
+    }
+
+    let dominators = Dominators::new(function);
+    // the blocks which can't be reached are never renamed, so they mustn't assign anything
+    for (id, block) in function.blocks.iter_mut().enumerate() {
+        if !dominators.is_reachable(BlockId(id)) {
+            *block = Block {
+                instructions: vec![],
+                terminator: Terminator::Unreachable,
+            };
+        }
+    }
+
+    let phis = place_phis(function, &dominators);
+    rename(function, &dominators, &phis);
+}
+
+/// Moves the entry to the end and jumps to it from a new entry without predecessors.
+fn move_entry(function: &mut Function) {
+    let moved = BlockId(function.blocks.len());
+    let entry = Block {
+        instructions: vec![],
+        terminator: Terminator::Jump(moved),
+    };
+    let old_entry = std::mem::replace(&mut function.blocks[0], entry);
+    function.blocks.push(old_entry);
+
+    for block in &mut function.blocks[1..] {
+        block
+            .terminator
+            .retarget(|target| if target.0 == 0 { moved } else { target });
+    }
+}
+
+/// Inserts the phis without arguments and returns the variable every phi of a block is for.
+fn place_phis(function: &mut Function, dominators: &Dominators) -> Vec<Vec<Var>> {
+    let var_count = function.var_count as usize;
+    let mut assignments = vec![Vec::new(); var_count];
+    for param in &function.params {
+        assignments[param.0 as usize].push(BlockId(0));
+    }
+    // the variables which are used before they're assigned in some block
+    let mut globals = HashSet::new();
+
+    for (id, block) in function.blocks.iter().enumerate() {
+        let mut assigned = HashSet::new();
+        let operands = block
+            .instructions
+            .iter()
+            .map(|instruction| (instruction.operands(), instruction.dest()));
+        for (operands, dest) in operands.chain([(block.terminator.operands(), None)]) {
+            for operand in operands {
+                if let Operand::Var(var) = operand
+                    && !assigned.contains(var)
+                {
+                    globals.insert(*var);
+                }
+            }
+            if let Some(dest) = dest
+                && assigned.insert(dest)
+            {
+                assignments[dest.0 as usize].push(BlockId(id));
+            }
+        }
+    }
+
+    let frontiers = dominators.frontiers(function);
+    let mut phis = vec![Vec::new(); function.blocks.len()];
+    let mut globals: Vec<_> = globals.into_iter().collect();
+    globals.sort();
+
+    for var in globals {
+        let mut worklist = assignments[var.0 as usize].clone();
+        let mut assigned: HashSet<_> = worklist.iter().copied().collect();
+        while let Some(block) = worklist.pop() {
+            for &frontier in &frontiers[block.0] {
+                if phis[frontier.0].contains(&var) {
+                    continue;
+                }
+                phis[frontier.0].push(var);
+                // the phi is another assignment of the variable
+                if assigned.insert(frontier) {
+                    worklist.push(frontier);
+                }
+            }
+        }
+    }
+
+    for (block, vars) in function.blocks.iter_mut().zip(&phis) {
+        let placed = vars.iter().map(|var| Instruction::Phi {
+            dest: *var,
+            args: vec![],
+        });
+        block.instructions.splice(0..0, placed);
+    }
+
+    phis
+}
+
+/// Gives every assignment a new variable and replaces the uses with the variable which reaches
+/// them, `phis` are the variables every phi of a block has been placed for.
+fn rename(function: &mut Function, dominators: &Dominators, phis: &[Vec<Var>]) {
+    let children = dominators.children();
+    // the current variable of every original one, the parameters keep theirs
+    let mut stacks = vec![Vec::new(); function.var_count as usize];
+    for param in &function.params {
+        stacks[param.0 as usize].push(*param);
+    }
+    let mut var_count = function.var_count;
+
+    // a block is left again after the blocks it dominates, which undoes its assignments
+    enum Visit {
+        Enter(BlockId),
+        Leave(Vec<Var>),
+    }
+    let mut visits = vec![Visit::Enter(BlockId(0))];
+
+    while let Some(visit) = visits.pop() {
+        let id = match visit {
+            Visit::Enter(id) => id,
+            Visit::Leave(assigned) => {
+                for var in assigned {
+                    stacks[var.0 as usize].pop();
+                }
+                continue;
+            }
+        };
+
+        let mut assigned = Vec::new();
+        let block = &mut function.blocks[id.0];
+        for instruction in &mut block.instructions {
+            if !matches!(instruction, Instruction::Phi { .. }) {
+                for operand in instruction.operands_mut() {
+                    *operand = current(operand, &stacks);
+                }
+            }
+            if let Some(dest) = dest_mut(instruction) {
+                let renamed = Var(var_count);
+                var_count += 1;
+                stacks[dest.0 as usize].push(renamed);
+                assigned.push(*dest);
+                *dest = renamed;
+            }
+        }
+        for operand in block.terminator.operands_mut() {
+            *operand = current(operand, &stacks);
+        }
+
+        let mut successors = block.terminator.successors();
+        successors.dedup();
+        for successor in successors {
+            let instructions = &mut function.blocks[successor.0].instructions;
+            for (instruction, var) in instructions.iter_mut().zip(&phis[successor.0]) {
+                let Instruction::Phi { args, .. } = instruction else {
+                    unreachable!("the phis are at the start of the block");
+                };
+                args.push((id, current(&Operand::Var(*var), &stacks)));
+            }
+        }
+
+        visits.push(Visit::Leave(assigned));
+        for &child in children[id.0].iter().rev() {
+            visits.push(Visit::Enter(child));
+        }
+    }
+
+    function.var_count = var_count;
+}
+
+/// The variable which holds the value of the original variable at this point.
+fn current(operand: &Operand, stacks: &[Vec<Var>]) -> Operand {
+    match operand {
+        Operand::Var(var) => match stacks[var.0 as usize].last() {
+            Some(current) => Operand::Var(*current),
+            // the variable isn't assigned on this path, so the value is never used
+            None => Operand::Const(0),
+        },
+        Operand::Const(_) => *operand,
+    }
+}
+
+fn dest_mut<'a>(instruction: &'a mut Instruction) -> Option<&'a mut Var> {
+    match instruction {
+        Instruction::Copy { dest, .. }
+        | Instruction::Binary { dest, .. }
+        | Instruction::String { dest, .. }
+        | Instruction::Read { dest }
+        | Instruction::Phi { dest, .. } => Some(dest),
+        Instruction::Call { dest, .. } => dest.as_mut(),
+        Instruction::Print { .. } => None,
+    }
+}
+
+fn out_of_ssa(function: &mut Function) {
+    split_edges(function);
+
+    for (id, predecessors) in function.predecessors().into_iter().enumerate() {
+        let instructions = &mut function.blocks[id].instructions;
+        let count = instructions
+            .iter()
+            .take_while(|instruction| matches!(instruction, Instruction::Phi { .. }))
+            .count();
+        let phis: Vec<_> = instructions.drain(..count).collect();
+
+        for predecessor in predecessors {
+            let copies = phis
+                .iter()
+                .filter_map(|phi| match phi {
+                    Instruction::Phi { dest, args } => args
+                        .iter()
+                        .find(|(block, _)| *block == predecessor)
+                        .map(|(_, arg)| (*dest, *arg)),
+                    _ => unreachable!("only the phis have been taken"),
+                })
+                .collect();
+            let copies = sequentialize(copies, function);
+            function.blocks[predecessor.0].instructions.extend(copies);
+        }
+    }
+}
+
+/// Splits the edges from a branch into a block with phis by a block which only jumps, so the
+/// copies of the phis can be placed in it.
+fn split_edges(function: &mut Function) {
+    for (id, predecessors) in function.predecessors().into_iter().enumerate() {
+        let has_phis = matches!(
+            function.blocks[id].instructions.first(),
+            Some(Instruction::Phi { .. })
+        );
+        if !has_phis {
+            continue;
+        }
+
+        let mut predecessors = predecessors;
+        predecessors.dedup();
+        for predecessor in predecessors {
+            if !matches!(
+                function.blocks[predecessor.0].terminator,
+                Terminator::Branch { .. }
+            ) {
+                continue;
+            }
+
+            let split = BlockId(function.blocks.len());
+            function.blocks.push(Block {
+                instructions: vec![],
+                terminator: Terminator::Jump(BlockId(id)),
+            });
+            function.blocks[predecessor.0]
+                .terminator
+                .retarget(|target| if target.0 == id { split } else { target });
+            for instruction in &mut function.blocks[id].instructions {
+                if let Instruction::Phi { args, .. } = instruction {
+                    for (block, _) in args {
+                        if *block == predecessor {
+                            *block = split;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Turns copies which happen at the same time into a sequence of copies.
+fn sequentialize(
+    copies: Vec<(Var, Operand)>,
+    function: &mut Function,
+) -> Vec<Instruction<'static>> {
+    let mut pending: Vec<_> = copies
+        .into_iter()
+        .filter(|(dest, src)| *src != Operand::Var(*dest))
+        .collect();
+    let mut sequence = Vec::with_capacity(pending.len());
+
+    while !pending.is_empty() {
+        // a copy can happen once no other copy reads the old value of its destination
+        let ready = pending
+            .iter()
+            .position(|(dest, _)| !pending.iter().any(|(_, src)| *src == Operand::Var(*dest)));
+
+        match ready {
+            Some(index) => {
+                let (dest, src) = pending.remove(index);
+                sequence.push(Instruction::Copy { dest, src });
+            }
+            None => {
+                // only cycles are left, the old value of one destination is saved to break one
+                let (dest, _) = pending[0];
+                let saved = function.new_var();
+                sequence.push(Instruction::Copy {
+                    dest: saved,
+                    src: Operand::Var(dest),
+                });
+                for (_, src) in &mut pending {
+                    if *src == Operand::Var(dest) {
+                        *src = Operand::Var(saved);
+                    }
+                }
+            }
+        }
+    }
+
+    sequence
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generator::{self, Rng};
+
+    /// `i = 0; do { i += 1 } while (i != n); return i`, with the check `b1` jumping back to `b1`
+    fn counter() -> Function<'static> {
+        let i = Var(1);
+        Function {
+            name: "count",
+            params: vec![Var(0)],
+            blocks: vec![
+                Block {
+                    instructions: vec![Instruction::Copy {
+                        dest: i,
+                        src: Operand::Const(0),
+                    }],
+                    terminator: Terminator::Jump(BlockId(1)),
+                },
+                Block {
+                    instructions: vec![
+                        Instruction::Binary {
+                            dest: i,
+                            op: BinOp::Add,
+                            left: Operand::Var(i),
+                            right: Operand::Const(1),
+                        },
+                        Instruction::Binary {
+                            dest: Var(2),
+                            op: BinOp::Ne,
+                            left: Operand::Var(i),
+                            right: Operand::Var(Var(0)),
+                        },
+                    ],
+                    terminator: Terminator::Branch {
+                        cond: Operand::Var(Var(2)),
+                        then: BlockId(1),
+                        otherwise: BlockId(2),
+                    },
+                },
+                Block {
+                    instructions: vec![],
+                    terminator: Terminator::Return(Some(Operand::Var(i))),
+                },
+            ],
+            var_count: 3,
+        }
+    }
+
+    #[test]
+    fn loop_round_trip() {
+        let mut function = counter();
+        into_ssa(&mut function);
+        assert_eq!(
+            function.to_string(),
+            "function count(%0) {
+b0:
+    %3 = 0
+    jump b1
+b1:
+    %4 = phi [b0: %3], [b1: %5]
+    %5 = add %4, 1
+    %6 = ne %5, %0
+    branch %6, b1, b2
+b2:
+    return %5
+}
+"
+        );
+
+        out_of_ssa(&mut function);
+        assert_eq!(
+            function.to_string(),
+            "function count(%0) {
+b0:
+    %3 = 0
+    %4 = %3
+    jump b1
+b1:
+    %5 = add %4, 1
+    %6 = ne %5, %0
+    branch %6, b3, b2
+b2:
+    return %5
+b3:
+    %4 = %5
+    jump b1
+}
+"
+        );
+    }
+
+    #[test]
+    fn loop_at_the_entry() {
+        // `do { i += 1 } while (i != n); return i` without the initialisation of `i`
+        let mut function = counter();
+        function.blocks.remove(0);
+        function.blocks[0]
+            .terminator
+            .retarget(|target| BlockId(target.0 - 1));
+
+        into_ssa(&mut function);
+        assert_eq!(
+            function.to_string(),
+            "function count(%0) {
+b0:
+    jump b2
+b1:
+    return %4
+b2:
+    %3 = phi [b0: 0], [b2: %4]
+    %4 = add %3, 1
+    %5 = ne %4, %0
+    branch %5, b2, b1
+}
+"
+        );
+    }
+
+    #[test]
+    fn swapped_copies() {
+        let mut function = counter();
+        let copies = vec![
+            (Var(0), Operand::Var(Var(1))),
+            (Var(1), Operand::Var(Var(0))),
+            (Var(2), Operand::Var(Var(0))),
+        ];
+
+        let sequence: Vec<_> = sequentialize(copies, &mut function)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(sequence, ["%2 = %0", "%3 = %0", "%0 = %1", "%1 = %3"]);
+    }
+
+    #[test]
+    fn single_assignment() {
+        for seed in 0..50 {
+            let program = generator::generate(&mut Rng::new(seed));
+            let mut ir = lower::lower(&program, true);
+            construct(&mut ir);
+
+            for function in &ir.functions {
+                let mut assigned: HashSet<_> = function.params.iter().copied().collect();
+                for instruction in function.blocks.iter().flat_map(|block| &block.instructions) {
+                    if let Some(dest) = instruction.dest() {
+                        assert!(assigned.insert(dest), "{} is assigned twice", dest);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,692 @@
+use std::fmt;
+
+use chumsky::{Parser, prelude::*};
+
+type LexError<'src> = extra::Err<Rich<'src, char>>;
+
+macro_rules! lexer {
+    ($lifetime: lifetime, $node: ty) => {
+        impl Parser<$lifetime, &$lifetime str, $node, LexError<$lifetime>>
+    }
+}
+
+pub type Spanned<T> = (T, SimpleSpan);
+
+const KEYWORDS: &[&str] = &[
+    "struct",
+    "if",
+    "else",
+    "while",
+    "for",
+    "continue",
+    "break",
+    "return",
+    "assert",
+    "true",
+    "false",
+    "NULL",
+    "print",
+    "read",
+    "alloc",
+    "alloc_array",
+    "int",
+    "bool",
+    "void",
+    "char",
+    "string",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<'src> {
+    Keyword(&'src str),
+    Ident(&'src str),
+    Decnum(&'src str),
+    /// The digits of the number without the `0x` prefix.
+    Hexnum(&'src str),
+    /// The body of the literal without the surrounding `'`.
+    Charconst(&'src str),
+    /// The body of the literal without the surrounding `"`.
+    Stringconst(&'src str),
+    /// Arithmetic, comparison and assignment operators.
+    Op(&'src str),
+    /// Delimiters and separators.
+    Ctrl(char),
+    /// `\result`
+    Result,
+    /// `\length`
+    Length,
+    /// `//@` or `/*@`
+    AnnotationStart,
+    /// The end of the line of a `//@` annotation or `@*/`.
+    AnnotationEnd,
+}
+
+impl fmt::Display for Token<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Keyword(s) | Token::Ident(s) | Token::Decnum(s) | Token::Op(s) => {
+                write!(f, "{}", s)
+            }
+            Token::Hexnum(s) => write!(f, "0x{}", s),
+            Token::Charconst(s) => write!(f, "'{}'", s),
+            Token::Stringconst(s) => write!(f, "\"{}\"", s),
+            Token::Ctrl(c) => write!(f, "{}", c),
+            Token::Result => write!(f, "\\result"),
+            Token::Length => write!(f, "\\length"),
+            Token::AnnotationStart => write!(f, "start of annotation"),
+            Token::AnnotationEnd => write!(f, "end of annotation"),
+        }
+    }
+}
+
+pub fn lex<'src>(code: &'src str) -> ParseResult<Vec<Spanned<Token<'src>>>, Rich<'src, char>> {
+    lexer().parse(code)
+}
+
+/// The comments between the tokens of the code, which the lexer skips like whitespace.
+pub fn comments<'src>(code: &'src str, tokens: &[Spanned<Token<'src>>]) -> Vec<Spanned<&'src str>> {
+    let mut comments = Vec::new();
+
+    let mut start = 0;
+    let gaps = tokens
+        .iter()
+        .map(|(_, span)| (span.start, span.end))
+        .chain([(code.len(), code.len())]);
+    for (end, next) in gaps {
+        let gap = &code[start..end];
+        let spans = padding_comments()
+            .parse(gap)
+            .into_output()
+            .unwrap_or_default();
+        comments.extend(spans.into_iter().map(|span| {
+            let span = SimpleSpan::from(start + span.start..start + span.end);
+            (&code[span.into_range()], span)
+        }));
+
+        start = next;
+    }
+
+    comments
+}
+
+/// The spans of the comments in padding.
+fn padding_comments<'src>() -> lexer!('src, Vec<SimpleSpan>) {
+    choice((
+        one_of(" \n\t\r").to(None),
+        comment_parser().map_with(|_, e| Some(e.span())),
+    ))
+    .repeated()
+    .collect::<Vec<_>>()
+    .map(|spans| spans.into_iter().flatten().collect())
+}
+
+fn lexer<'src>() -> lexer!('src, Vec<Spanned<Token<'src>>>) {
+    let token = token_parser()
+        .map_with(|token, e| (token, e.span()))
+        .boxed();
+
+    // the end of the line is part of the annotation
+    let line_annotation = just("//@")
+        .map_with(|_, e| (Token::AnnotationStart, e.span()))
+        .then_ignore(inline_padding())
+        .then(
+            token
+                .clone()
+                .then_ignore(inline_padding())
+                .repeated()
+                .collect::<Vec<_>>(),
+        )
+        .then(
+            choice((just('\n').ignored(), end())).map_with(|_, e| (Token::AnnotationEnd, e.span())),
+        )
+        .map(|((start, tokens), end)| [vec![start], tokens, vec![end]].concat());
+
+    let block_annotation = just("/*@")
+        .map_with(|_, e| (Token::AnnotationStart, e.span()))
+        .then_ignore(padding())
+        .then(
+            token
+                .clone()
+                .then_ignore(padding())
+                .repeated()
+                .collect::<Vec<_>>(),
+        )
+        .then(just("@*/").map_with(|_, e| (Token::AnnotationEnd, e.span())))
+        .map(|((start, tokens), end)| [vec![start], tokens, vec![end]].concat());
+
+    let item = choice((
+        line_annotation,
+        block_annotation,
+        token.map(|token| vec![token]),
+    ));
+
+    padding()
+        .ignore_then(item.then_ignore(padding()).repeated().collect::<Vec<_>>())
+        .then_ignore(end())
+        .map(|tokens| tokens.concat())
+}
+
+/// Tokens are lexed by maximal munch: a keyword, number or `\result` which runs into further
+/// identifier characters isn't split up, e.g. `intx` is an identifier and `1x` is an error.
+fn token_parser<'src>() -> lexer!('src, Token<'src>) {
+    let ident = ident_parser().map(|ident| match KEYWORDS.contains(&ident) {
+        true => Token::Keyword(ident),
+        false => Token::Ident(ident),
+    });
+
+    let number = choice((
+        hexnum_parser().map(Token::Hexnum),
+        decnum_parser().map(Token::Decnum),
+    ))
+    .then_ignore(word_boundary());
+
+    let special = just('\\')
+        .ignore_then(choice((
+            just("result").to(Token::Result),
+            just("length").to(Token::Length),
+        )))
+        .then_ignore(word_boundary());
+
+    // longer operators have to be tried first
+    let op = choice((
+        just("+="),
+        just("-="),
+        just("*="),
+        just("/="),
+        just("%="),
+        just("=="),
+        just("!="),
+        just("+"),
+        just("-"),
+        just("*"),
+        just("/"),
+        just("%"),
+        just("="),
+    ))
+    .map(Token::Op);
+
+    let ctrl = one_of("(){};,").map(Token::Ctrl);
+
+    choice((
+        number,
+        charconst_parser().map(Token::Charconst),
+        stringconst_parser().map(Token::Stringconst),
+        ident,
+        special,
+        op,
+        ctrl,
+    ))
+}
+
+fn padding<'src>() -> lexer!('src, ()) {
+    one_of(" \n\t\r")
+        .ignored()
+        .or(comment_parser())
+        .repeated()
+        .ignored()
+}
+
+/// Padding which doesn't span multiple lines, used inside of `//@` annotations.
+fn inline_padding<'src>() -> lexer!('src, ()) {
+    one_of(" \t\r")
+        .ignored()
+        .or(comment_parser())
+        .repeated()
+        .ignored()
+}
+
+fn block_comment<'src>() -> lexer!('src, ()) {
+    recursive(|block_comment| {
+        let block_comment_body = any()
+            .and_is(just("/*").not())
+            .and_is(just("*/").not())
+            .ignored();
+
+        just("/*")
+            .ignored()
+            .then_ignore(block_comment_body.or(block_comment).repeated())
+            .then_ignore(just("*/"))
+    })
+}
+
+fn line_comments<'src>() -> lexer!('src, ()) {
+    just("//")
+        .ignored()
+        .then_ignore(any().and_is(just("\n").not()).repeated())
+}
+
+fn comment_parser<'src>() -> lexer!('src, ()) {
+    let annotation_start = choice((just("//@"), just("/*@")));
+
+    choice((block_comment(), line_comments())).and_is(annotation_start.not())
+}
+
+fn ident_parser<'src>() -> lexer!('src, &'src str) {
+    #[rustfmt::skip]
+    let prefix = {
+        choice( (
+            one_of('A'..='Z'),
+            one_of('a'..='z'),
+            just('_')
+        ))
+    };
+
+    prefix.then(ident_char().repeated()).to_slice()
+}
+
+fn ident_char<'src>() -> lexer!('src, char) {
+    choice((
+        one_of('A'..='Z'),
+        one_of('a'..='z'),
+        one_of('0'..='9'),
+        just('_'),
+    ))
+}
+
+/// Succeeds without consuming anything if no identifier character follows.
+fn word_boundary<'src>() -> lexer!('src, ()) {
+    ident_char().not()
+}
+
+fn decnum_parser<'src>() -> lexer!('src, &'src str) {
+    let decnum = one_of('1'..='9')
+        .then(one_of('0'..='9').repeated())
+        .to_slice();
+
+    let just_zero = just("0");
+
+    choice((decnum, just_zero))
+}
+
+#[rustfmt::skip]
+fn hexnum_parser<'src>() -> lexer!('src, &'src str) {
+    just('0')
+        .ignore_then(one_of("xX").ignored())
+        .ignore_then(
+            choice((
+                one_of('A'..='F'),
+                one_of('a'..='f'),
+                one_of('0'..='9'))
+            )
+            .repeated()
+            .at_least(1)
+            .to_slice()
+        )
+}
+
+/// Printable ASCII characters which may appear unescaped in a literal delimited by `quote`.
+fn literal_char_parser<'src>(quote: char) -> lexer!('src, ()) {
+    any().try_map(move |c: char, span| {
+        let message = match c {
+            ' '..='~' if c != quote && c != '\\' => return Ok(()),
+            // the closing quote of a string always ends it
+            '\'' if c == quote => "a character literal can't be empty".to_string(),
+            _ => format!("{:?} isn't a printable ASCII character", c),
+        };
+
+        Err(Rich::custom(span, message))
+    })
+}
+
+fn escape_parser<'src>() -> lexer!('src, ()) {
+    just('\\').ignore_then(one_of("ntvbrfa'\"\\")).ignored()
+}
+
+fn charconst_parser<'src>() -> lexer!('src, &'src str) {
+    let null = just("\\0").ignored();
+
+    choice((escape_parser(), null, literal_char_parser('\'')))
+        .to_slice()
+        .delimited_by(just('\''), just('\''))
+}
+
+fn stringconst_parser<'src>() -> lexer!('src, &'src str) {
+    choice((escape_parser(), literal_char_parser('"')))
+        .repeated()
+        .to_slice()
+        .delimited_by(just('"'), just('"'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(code: &str) -> Vec<Token<'_>> {
+        lex(code)
+            .into_result()
+            .unwrap()
+            .into_iter()
+            .map(|(token, _)| token)
+            .collect()
+    }
+
+    // == tokens
+    #[test]
+    fn tokens_simple() {
+        assert_eq!(
+            tokens("int main() { return 0x1f; }"),
+            vec![
+                Token::Keyword("int"),
+                Token::Ident("main"),
+                Token::Ctrl('('),
+                Token::Ctrl(')'),
+                Token::Ctrl('{'),
+                Token::Keyword("return"),
+                Token::Hexnum("1f"),
+                Token::Ctrl(';'),
+                Token::Ctrl('}'),
+            ]
+        );
+    }
+
+    #[test]
+    fn tokens_spans() {
+        assert_eq!(
+            lex(" x += 1").into_result(),
+            Ok(vec![
+                (Token::Ident("x"), SimpleSpan::from(1..2)),
+                (Token::Op("+="), SimpleSpan::from(3..5)),
+                (Token::Decnum("1"), SimpleSpan::from(6..7)),
+            ])
+        );
+    }
+
+    #[test]
+    fn tokens_comments_between_operators() {
+        assert_eq!(
+            tokens("x /* c */ += // c\n 1"),
+            vec![Token::Ident("x"), Token::Op("+="), Token::Decnum("1")]
+        );
+    }
+
+    #[test]
+    fn tokens_invalid_character() {
+        assert!(lex("int x = 1 # 2;").into_result().is_err());
+    }
+
+    // == annotations
+    #[test]
+    fn line_annotation() {
+        assert_eq!(
+            tokens("//@requires \\result == 0; // c\nx"),
+            vec![
+                Token::AnnotationStart,
+                Token::Ident("requires"),
+                Token::Result,
+                Token::Op("=="),
+                Token::Decnum("0"),
+                Token::Ctrl(';'),
+                Token::AnnotationEnd,
+                Token::Ident("x"),
+            ]
+        );
+    }
+
+    #[test]
+    fn block_annotation() {
+        assert_eq!(
+            tokens("/*@ assert\n\\length(x); @*/"),
+            vec![
+                Token::AnnotationStart,
+                Token::Keyword("assert"),
+                Token::Length,
+                Token::Ctrl('('),
+                Token::Ident("x"),
+                Token::Ctrl(')'),
+                Token::Ctrl(';'),
+                Token::AnnotationEnd,
+            ]
+        );
+    }
+
+    #[test]
+    fn annotations_are_no_comments() {
+        assert!(padding().parse("//@assert true;").into_result().is_err());
+    }
+
+    // == ident
+    #[test]
+    fn ident_simple() {
+        assert_eq!(ident_parser().parse("hello").into_result(), Ok("hello"));
+    }
+
+    #[test]
+    fn ident_full() {
+        assert_eq!(ident_parser().parse("AZaz_0").into_result(), Ok("AZaz_0"));
+    }
+
+    #[test]
+    fn ident_invalid() {
+        assert!(ident_parser().parse("0no").into_result().is_err());
+    }
+
+    #[test]
+    fn ident_keyword() {
+        assert_eq!(tokens("struct"), vec![Token::Keyword("struct")]);
+    }
+
+    // == maximal munch
+    #[test]
+    fn keyword_prefix_of_ident() {
+        assert_eq!(
+            tokens("intx = 3;"),
+            vec![
+                Token::Ident("intx"),
+                Token::Op("="),
+                Token::Decnum("3"),
+                Token::Ctrl(';')
+            ]
+        );
+    }
+
+    #[test]
+    fn return_prefix_of_ident() {
+        assert_eq!(
+            tokens("returnx;"),
+            vec![Token::Ident("returnx"), Token::Ctrl(';')]
+        );
+    }
+
+    #[test]
+    fn return_followed_by_non_ident_char() {
+        assert_eq!(
+            tokens("return(x);"),
+            vec![
+                Token::Keyword("return"),
+                Token::Ctrl('('),
+                Token::Ident("x"),
+                Token::Ctrl(')'),
+                Token::Ctrl(';')
+            ]
+        );
+    }
+
+    #[test]
+    fn keyword_with_digit_suffix() {
+        assert_eq!(tokens("int1"), vec![Token::Ident("int1")]);
+    }
+
+    #[test]
+    fn number_followed_by_ident() {
+        assert!(lex("1x").into_result().is_err());
+    }
+
+    #[test]
+    fn leading_zero() {
+        assert!(lex("0123").into_result().is_err());
+    }
+
+    #[test]
+    fn hexnum_followed_by_ident() {
+        assert!(lex("0xfg").into_result().is_err());
+    }
+
+    #[test]
+    fn result_followed_by_ident() {
+        assert!(lex("\\resultx").into_result().is_err());
+    }
+
+    // == decnum
+    #[test]
+    fn decnum_parser_zero() {
+        assert_eq!(decnum_parser().parse("0").into_result(), Ok("0"));
+    }
+
+    #[test]
+    fn decnum_parser_valid_number() {
+        assert_eq!(decnum_parser().parse("123").into_result(), Ok("123"));
+    }
+
+    #[test]
+    fn decnum_parser_invalid_number() {
+        assert!(decnum_parser().parse("0123").into_result().is_err());
+    }
+
+    // == hexnum
+    #[test]
+    fn hexnum_parser_simple() {
+        assert_eq!(hexnum_parser().parse("0xabc").into_result(), Ok("abc"));
+    }
+
+    #[test]
+    fn hexnum_parser_big_x() {
+        assert_eq!(hexnum_parser().parse("0Xabc").into_result(), Ok("abc"));
+    }
+
+    #[test]
+    fn hexnum_parser_missing_numbers() {
+        assert!(hexnum_parser().parse("0x").into_result().is_err());
+    }
+
+    // == charconst
+    #[test]
+    fn charconst_simple() {
+        assert_eq!(charconst_parser().parse("'a'").into_result(), Ok("a"));
+    }
+
+    #[test]
+    fn charconst_escape() {
+        assert_eq!(charconst_parser().parse(r"'\0'").into_result(), Ok(r"\0"));
+    }
+
+    #[test]
+    fn charconst_invalid_escape() {
+        assert!(charconst_parser().parse(r"'\q'").into_result().is_err());
+    }
+
+    #[test]
+    fn charconst_empty() {
+        assert!(charconst_parser().parse("''").into_result().is_err());
+
+        let errors = lex("char c = '';").into_result().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].to_string(), "a character literal can't be empty");
+        assert_eq!(errors[0].span().into_range(), 10..11);
+    }
+
+    // == stringconst
+    #[test]
+    fn stringconst_escapes() {
+        assert_eq!(
+            stringconst_parser().parse(r#""a\"b\n""#).into_result(),
+            Ok(r#"a\"b\n"#)
+        );
+    }
+
+    #[test]
+    fn stringconst_non_ascii() {
+        let errors = lex("\"aé\"").into_result().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].to_string(),
+            "'é' isn't a printable ASCII character"
+        );
+        assert_eq!(errors[0].span().into_range(), 2..4);
+    }
+
+    #[test]
+    fn stringconst_empty() {
+        assert_eq!(stringconst_parser().parse(r#""""#).into_result(), Ok(""));
+    }
+
+    #[test]
+    fn stringconst_null_escape() {
+        assert!(stringconst_parser().parse(r#""\0""#).into_result().is_err());
+    }
+
+    #[test]
+    fn stringconst_comment() {
+        assert_eq!(
+            tokens(r#""// no comment""#),
+            vec![Token::Stringconst("// no comment")]
+        );
+    }
+
+    // == comments
+    #[test]
+    fn block_comments_1_level() {
+        assert_eq!(
+            block_comment().parse("/* hello there */").into_result(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn block_comments_2_level() {
+        assert_eq!(
+            block_comment()
+                .parse("/* /* hello */ /* there */ */")
+                .into_result(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn block_comments_doom_level() {
+        assert_eq!(
+            block_comment()
+                .parse("/* :peepoScream: /* hello /* */ */ :peepoNo /* there */ */")
+                .into_result(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn comments_between_tokens() {
+        let code = "/* a /* b */ */ x // c\n//@ requires /* d */ x;\n// e";
+        let tokens = lex(code).into_result().unwrap();
+        let comments: Vec<&str> = comments(code, &tokens)
+            .into_iter()
+            .map(|(comment, _)| comment)
+            .collect();
+
+        assert_eq!(comments, vec!["/* a /* b */ */", "// c", "/* d */", "// e"]);
+    }
+
+    #[test]
+    fn block_comments_open_too_much() {
+        assert!(block_comment().parse("/* /* no */").into_result().is_err());
+    }
+
+    #[test]
+    fn block_comments_close_too_much() {
+        assert!(block_comment().parse("/* no */ */").into_result().is_err());
+    }
+
+    // == line comments
+    #[test]
+    fn line_comments_simple() {
+        assert_eq!(
+            line_comments().parse("// rofl lmao kekw").into_result(),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn line_comments_nested() {
+        assert_eq!(
+            line_comments().parse("// rofl // lmao kekw").into_result(),
+            Ok(())
+        );
+    }
+}
@@ -0,0 +1,122 @@
+//! A compiler for C0, the language of the `Compiler Design` lecture.
+//!
+//! The functions of this crate run the stages of the compiler one by one, the binary wraps them in
+//! a command line interface.
+mod analyzer;
+pub mod cli;
+pub mod codegen;
+pub mod differential;
+pub mod driver;
+pub mod formatter;
+pub mod generator;
+pub mod grammar;
+pub mod interpreter;
+pub mod ir;
+mod lexer;
+pub mod library;
+mod optimize;
+mod parser;
+mod passes;
+pub mod test_runner;
+
+pub use driver::{CompileError, CompileOutcome};
+pub use interpreter::interpret;
+
+/// Lexes and parses the code.
+/// The errors of a program which can't be lexed or parsed are reported as
+/// [CompileError::Lexing] or [CompileError::Parsing].
+pub fn parse(code: &str) -> Result<grammar::Program<'_>, CompileError> {
+    parse_tokens(code, lex(code)?)
+}
+
+fn lex(code: &str) -> Result<Vec<lexer::Spanned<lexer::Token<'_>>>, CompileError> {
+    let (tokens, errors) = lexer::lex(code).into_output_errors();
+    match tokens {
+        Some(tokens) if errors.is_empty() => Ok(tokens),
+        _ => Err(CompileError::Lexing(driver::messages(code, errors))),
+    }
+}
+
+fn parse_tokens<'src>(
+    code: &'src str,
+    tokens: Vec<lexer::Spanned<lexer::Token<'src>>>,
+) -> Result<grammar::Program<'src>, CompileError> {
+    match parser::parse_tokens(tokens, code.len()) {
+        (Some(program), errors) if errors.is_empty() => Ok(program),
+        (_, errors) => Err(CompileError::Parsing(driver::messages(code, errors))),
+    }
+}
+
+/// Runs the semantic checks which every program has to pass before it's lowered or interpreted.
+pub fn analyze(program: &grammar::Program<'_>) -> Result<(), CompileError> {
+    analyzer::analyze(program).map_err(|check| CompileError::Semantic { check })
+}
+
+/// Lowers the analyzed program into the IR without optimising it.
+/// The annotations are only checked at runtime if `dynamic_checks` is set.
+pub fn lower<'src>(program: &grammar::Program<'src>, dynamic_checks: bool) -> ir::Program<'src> {
+    ir::lower::lower(program, dynamic_checks)
+}
+
+/// Runs all stages up to the x86-64 assembly, including the optimisations.
+/// The assembly can be linked with [codegen::toolchain::link].
+pub fn compile_to_asm(code: &str, dynamic_checks: bool) -> Result<String, CompileError> {
+    let program = parse(code)?;
+    let options = passes::Options {
+        dynamic_checks,
+        ..Default::default()
+    };
+    let ir = passes::PassManager::compiler(options)
+        .run(&program)
+        .map_err(|check| CompileError::Semantic { check })?
+        .expect("the compiler lowers the program");
+
+    Ok(codegen::asm(&ir))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stages() {
+        let program = parse("int main() { print(1 + 2); return 0; }").unwrap();
+        assert!(analyze(&program).is_ok());
+        assert!(lower(&program, false).to_string().contains("print int %0"));
+
+        let mut output = Vec::new();
+        let exit_code = interpreter::interpret_with_io(
+            &program,
+            interpreter::Options::default(),
+            &mut &b""[..],
+            &mut output,
+        );
+        assert_eq!(exit_code, Ok(0));
+        assert_eq!(output, b"3\n");
+    }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            parse("int main() { return 1 $ 2; }"),
+            Err(CompileError::Lexing(_))
+        ));
+        assert!(matches!(
+            parse("int main() { return 1 2; }"),
+            Err(CompileError::Parsing(_))
+        ));
+        assert!(matches!(
+            compile_to_asm("int main() { return x; }", false),
+            Err(CompileError::Semantic {
+                check: "variable_dec_init_and_usage"
+            })
+        ));
+    }
+
+    #[test]
+    fn asm() {
+        let asm = compile_to_asm("int main() { return 0; }", false).unwrap();
+
+        assert!(asm.contains("c0_main:"));
+    }
+}
@@ -0,0 +1,69 @@
+use crate::grammar::Type;
+
+/// The functions of the C0 standard library which every program can call without declaring them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Builtin {
+    StringLength,
+    StringCharat,
+    StringJoin,
+    StringSub,
+    StringEqual,
+    StringCompare,
+    StringFromint,
+    StringFromchar,
+    CharOrd,
+    CharChr,
+}
+
+impl Builtin {
+    pub const ALL: &[Builtin] = &[
+        Builtin::StringLength,
+        Builtin::StringCharat,
+        Builtin::StringJoin,
+        Builtin::StringSub,
+        Builtin::StringEqual,
+        Builtin::StringCompare,
+        Builtin::StringFromint,
+        Builtin::StringFromchar,
+        Builtin::CharOrd,
+        Builtin::CharChr,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|builtin| builtin.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Builtin::StringLength => "string_length",
+            Builtin::StringCharat => "string_charat",
+            Builtin::StringJoin => "string_join",
+            Builtin::StringSub => "string_sub",
+            Builtin::StringEqual => "string_equal",
+            Builtin::StringCompare => "string_compare",
+            Builtin::StringFromint => "string_fromint",
+            Builtin::StringFromchar => "string_fromchar",
+            Builtin::CharOrd => "char_ord",
+            Builtin::CharChr => "char_chr",
+        }
+    }
+
+    /// Returns the types of the parameters and the return type.
+    pub fn signature(&self) -> (&'static [Type], Type) {
+        match self {
+            Builtin::StringLength => (&[Type::String], Type::Int),
+            Builtin::StringCharat => (&[Type::String, Type::Int], Type::Char),
+            Builtin::StringJoin => (&[Type::String, Type::String], Type::String),
+            Builtin::StringSub => (&[Type::String, Type::Int, Type::Int], Type::String),
+            Builtin::StringEqual => (&[Type::String, Type::String], Type::Bool),
+            Builtin::StringCompare => (&[Type::String, Type::String], Type::Int),
+            Builtin::StringFromint => (&[Type::Int], Type::String),
+            Builtin::StringFromchar => (&[Type::Char], Type::String),
+            Builtin::CharOrd => (&[Type::Char], Type::Int),
+            Builtin::CharChr => (&[Type::Int], Type::Char),
+        }
+    }
+}
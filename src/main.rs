@@ -1,24 +1,56 @@
-mod analyzer;
-mod grammar;
-mod parser;
+use pimpf::{
+    CompileError, CompileOutcome,
+    cli::{self, Command},
+    differential, driver, formatter, test_runner,
+};
 
 fn main() {
-    let mut args = std::env::args();
-
-    let input_file = std::fs::read_to_string(args.nth(1).unwrap()).unwrap();
-    // let output_file = args.nth(2).unwrap();
+    let cli = match cli::parse(std::env::args().skip(1)) {
+        Ok(Command::Compile(cli)) => cli,
+        Ok(Command::Test {
+            dir,
+            timeout,
+            differential,
+        }) => {
+            let compiler = match std::env::current_exe() {
+                Ok(compiler) => compiler,
+                Err(error) => {
+                    eprintln!("couldn't find the compiler: {}", error);
+                    std::process::exit(2);
+                }
+            };
+            let options = test_runner::Options {
+                dir,
+                compiler,
+                timeout,
+            };
+            let exit_code = match differential {
+                true => differential::run(&options),
+                false => test_runner::run(&options),
+            };
+            std::process::exit(exit_code);
+        }
+        Ok(Command::Fmt { inputs, check }) => {
+            std::process::exit(formatter::run(&inputs, check));
+        }
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return;
+        }
+        Err(message) => {
+            let error = CompileError::Usage(message);
+            eprintln!("{}\n\n{}", error, cli::USAGE);
+            std::process::exit(error.exit_code());
+        }
+    };
 
-    // compile stuff
-    let result = parser::parse(&input_file);
-    if result.has_errors() {
-        for error in result.errors() {
+    match driver::compile(&cli) {
+        Ok(CompileOutcome::Emitted) => {}
+        Ok(CompileOutcome::Interpreted(Ok(exit_code))) => std::process::exit(exit_code),
+        Ok(CompileOutcome::Interpreted(Err(exception))) => exception.raise(),
+        Err(error) => {
             eprintln!("{}", error);
+            std::process::exit(error.exit_code());
         }
-
-        std::process::exit(42);
-    }
-
-    if analyzer::analyze(result.output().unwrap()).is_err() {
-        std::process::exit(7);
     }
 }
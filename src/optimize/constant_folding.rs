@@ -0,0 +1,320 @@
+//! Computes operations on constants and propagates the constant values of variables.
+//!
+//! The values are found by a dataflow analysis over the control flow graph, so a variable stays
+//! constant across blocks as long as every path assigns it the same value. A branch on a constant
+//! only continues on one side, which becomes a jump. Operations which trap, like a division by
+//! zero or `i32::MIN / -1`, are kept so the program still traps at runtime.
+
+use crate::ir::*;
+
+/// What's known about the value of a variable at some point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Value {
+    /// The variable hasn't been assigned on any path so far.
+    Unassigned,
+    Const(i32),
+    /// The variable may have different values.
+    Varying,
+}
+
+impl Value {
+    fn meet(self, other: Value) -> Value {
+        match (self, other) {
+            (Value::Unassigned, value) | (value, Value::Unassigned) => value,
+            (Value::Const(left), Value::Const(right)) if left == right => self,
+            _ => Value::Varying,
+        }
+    }
+}
+
+/// The values of all variables of a function.
+type State = Vec<Value>;
+
+pub fn run(program: &mut Program) {
+    for function in &mut program.functions {
+        let states = analyze(function);
+        for (block, state) in function.blocks.iter_mut().zip(states) {
+            // the blocks which can't be reached are left alone
+            if let Some(mut state) = state {
+                fold(block, &mut state);
+            }
+        }
+    }
+}
+
+/// Finds the values of the variables at the start of every block, `None` if it can't be reached.
+fn analyze(function: &Function) -> Vec<Option<State>> {
+    let mut entry = vec![Value::Unassigned; function.var_count as usize];
+    for param in &function.params {
+        entry[param.0 as usize] = Value::Varying;
+    }
+
+    let mut states = vec![None; function.blocks.len()];
+    states[0] = Some(entry);
+    let mut worklist = vec![BlockId(0)];
+
+    while let Some(id) = worklist.pop() {
+        let block = &function.blocks[id.0];
+        let mut state = states[id.0]
+            .clone()
+            .expect("only reached blocks are visited");
+        for instruction in &block.instructions {
+            transfer(instruction, &mut state);
+        }
+
+        for successor in successors(&block.terminator, &state) {
+            let changed = match &mut states[successor.0] {
+                Some(successor_state) => {
+                    let mut changed = false;
+                    for (value, incoming) in successor_state.iter_mut().zip(&state) {
+                        let met = value.meet(*incoming);
+                        changed |= met != *value;
+                        *value = met;
+                    }
+                    changed
+                }
+                successor_state => {
+                    *successor_state = Some(state.clone());
+                    true
+                }
+            };
+
+            if changed && !worklist.contains(&successor) {
+                worklist.push(successor);
+            }
+        }
+    }
+
+    states
+}
+
+/// The successors of a block, only one of them if it branches on a constant.
+fn successors(terminator: &Terminator, state: &State) -> Vec<BlockId> {
+    match terminator {
+        Terminator::Branch {
+            cond,
+            then,
+            otherwise,
+        } => match value(cond, state) {
+            Value::Const(0) => vec![*otherwise],
+            Value::Const(_) => vec![*then],
+            Value::Unassigned | Value::Varying => vec![*then, *otherwise],
+        },
+        terminator => terminator.successors(),
+    }
+}
+
+fn value(operand: &Operand, state: &State) -> Value {
+    match operand {
+        Operand::Const(value) => Value::Const(*value),
+        Operand::Var(var) => state[var.0 as usize],
+    }
+}
+
+fn transfer(instruction: &Instruction, state: &mut State) {
+    let Some(dest) = instruction.dest() else {
+        return;
+    };
+
+    state[dest.0 as usize] = match instruction {
+        Instruction::Copy { src, .. } => value(src, state),
+        Instruction::Binary {
+            op, left, right, ..
+        } => match (value(left, state), value(right, state)) {
+            (Value::Const(left), Value::Const(right)) => {
+                op.eval(left, right).map_or(Value::Varying, Value::Const)
+            }
+            _ => Value::Varying,
+        },
+        // in SSA form every variable is only assigned once, so its value in the state is the one
+        // it has at the end of the predecessor
+        Instruction::Phi { args, .. } => args.iter().fold(Value::Unassigned, |met, (_, arg)| {
+            met.meet(value(arg, state))
+        }),
+        Instruction::String { .. } | Instruction::Call { .. } | Instruction::Read { .. } => {
+            Value::Varying
+        }
+        Instruction::Print { .. } => unreachable!("prints don't assign a variable"),
+    };
+}
+
+/// Replaces the variables with a constant value by the value and computes the operations on
+/// constants, the state is the one at the start of the block.
+fn fold(block: &mut Block, state: &mut State) {
+    for instruction in &mut block.instructions {
+        replace(instruction.operands_mut(), state);
+
+        if let Instruction::Binary {
+            dest,
+            op,
+            left: Operand::Const(left),
+            right: Operand::Const(right),
+        } = instruction
+            && let Some(value) = op.eval(*left, *right)
+        {
+            *instruction = Instruction::Copy {
+                dest: *dest,
+                src: Operand::Const(value),
+            };
+        }
+
+        transfer(instruction, state);
+    }
+
+    replace(block.terminator.operands_mut(), state);
+    if let Terminator::Branch {
+        cond: Operand::Const(cond),
+        then,
+        otherwise,
+    } = block.terminator
+    {
+        block.terminator = Terminator::Jump(if cond != 0 { then } else { otherwise });
+    }
+}
+
+fn replace(operands: Vec<&mut Operand>, state: &State) {
+    for operand in operands {
+        if let Value::Const(value) = value(operand, state) {
+            *operand = Operand::Const(value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fold(code: &str, dynamic_checks: bool) -> String {
+        let program = crate::parse(code).unwrap();
+        let mut ir = crate::ir::lower::lower(&program, dynamic_checks);
+        run(&mut ir);
+
+        ir.functions.last().unwrap().to_string()
+    }
+
+    #[test]
+    fn wrapping_arithmetic() {
+        assert_eq!(
+            fold(
+                "int main() { int x = 2147483647 + 1; print(-x); return x * 3 % 7 - -7 / 2; }",
+                false
+            ),
+            "function main() {
+b0:
+    %0 = -2147483648
+    %1 = -2147483648
+    %2 = -2147483648
+    print int -2147483648
+    %3 = -2147483648
+    %4 = -2
+    %5 = -7
+    %6 = -3
+    %7 = 1
+    return 1
+b1:
+    unreachable
+}
+"
+        );
+    }
+
+    #[test]
+    fn trapping_operations_stay() {
+        assert_eq!(
+            fold(
+                "int main() { int zero = 0; int min = -2147483648; \
+                 print(1 / zero); print(min % -1); return 0; }",
+                false
+            ),
+            "function main() {
+b0:
+    %0 = 0
+    %1 = -2147483648
+    %2 = -2147483648
+    %3 = div 1, 0
+    print int %3
+    %4 = -1
+    %5 = mod -2147483648, -1
+    print int %5
+    return 0
+b1:
+    unreachable
+}
+"
+        );
+    }
+
+    #[test]
+    fn across_blocks() {
+        assert_eq!(
+            fold(
+                "int f(int p) { int x = 3; assert(p == 1); assert(x == 3); return x + p; }",
+                false
+            ),
+            "function f(%0) {
+b0:
+    %1 = 3
+    %2 = eq %0, 1
+    branch %2, b2, b1
+b1:
+    abort
+b2:
+    %3 = 1
+    jump b3
+b3:
+    %4 = add 3, %0
+    return %4
+b4:
+    unreachable
+}
+"
+        );
+    }
+
+    #[test]
+    fn different_values_on_paths() {
+        // x is 1 or 2 depending on the branch
+        let blocks = vec![
+            Block {
+                instructions: vec![Instruction::Read { dest: Var(0) }],
+                terminator: Terminator::Branch {
+                    cond: Operand::Var(Var(0)),
+                    then: BlockId(1),
+                    otherwise: BlockId(2),
+                },
+            },
+            Block {
+                instructions: vec![Instruction::Copy {
+                    dest: Var(1),
+                    src: Operand::Const(1),
+                }],
+                terminator: Terminator::Jump(BlockId(3)),
+            },
+            Block {
+                instructions: vec![Instruction::Copy {
+                    dest: Var(1),
+                    src: Operand::Const(2),
+                }],
+                terminator: Terminator::Jump(BlockId(3)),
+            },
+            Block {
+                instructions: vec![],
+                terminator: Terminator::Return(Some(Operand::Var(Var(1)))),
+            },
+        ];
+        let mut ir = Program {
+            functions: vec![Function {
+                name: "main",
+                params: vec![],
+                blocks,
+                var_count: 2,
+            }],
+        };
+        run(&mut ir);
+
+        assert_eq!(
+            ir.functions[0].blocks[3].terminator,
+            Terminator::Return(Some(Operand::Var(Var(1))))
+        );
+    }
+}
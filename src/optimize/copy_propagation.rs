@@ -0,0 +1,112 @@
+//! Replaces the uses of variables which hold a copy of another operand by that operand.
+//! The IR might not be in SSA form, so copies are only tracked within a block.
+
+use std::collections::HashMap;
+
+use crate::ir::*;
+
+pub fn run(program: &mut Program) {
+    for function in &mut program.functions {
+        for block in &mut function.blocks {
+            propagate(block);
+        }
+    }
+}
+
+fn propagate(block: &mut Block) {
+    // the operand every variable is currently a copy of
+    let mut copies: HashMap<Var, Operand> = HashMap::new();
+
+    for instruction in &mut block.instructions {
+        for operand in instruction.operands_mut() {
+            replace(operand, &copies);
+        }
+
+        let Some(dest) = instruction.dest() else {
+            continue;
+        };
+
+        // the copies of the old value are gone
+        copies.retain(|var, src| *var != dest && *src != Operand::Var(dest));
+        if let Instruction::Copy { src, .. } = instruction
+            && *src != Operand::Var(dest)
+        {
+            copies.insert(dest, *src);
+        }
+    }
+
+    for operand in block.terminator.operands_mut() {
+        replace(operand, &copies);
+    }
+}
+
+fn replace(operand: &mut Operand, copies: &HashMap<Var, Operand>) {
+    if let Operand::Var(var) = operand
+        && let Some(src) = copies.get(var)
+    {
+        *operand = *src;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn propagate_copies() {
+        let mut block = Block {
+            instructions: vec![
+                Instruction::Copy {
+                    dest: Var(1),
+                    src: Operand::Var(Var(0)),
+                },
+                Instruction::Copy {
+                    dest: Var(2),
+                    src: Operand::Const(3),
+                },
+                Instruction::Binary {
+                    dest: Var(3),
+                    op: BinOp::Add,
+                    left: Operand::Var(Var(1)),
+                    right: Operand::Var(Var(2)),
+                },
+            ],
+            terminator: Terminator::Return(Some(Operand::Var(Var(2)))),
+        };
+        propagate(&mut block);
+
+        assert_eq!(
+            block.instructions[2],
+            Instruction::Binary {
+                dest: Var(3),
+                op: BinOp::Add,
+                left: Operand::Var(Var(0)),
+                right: Operand::Const(3),
+            }
+        );
+        assert_eq!(
+            block.terminator,
+            Terminator::Return(Some(Operand::Const(3)))
+        );
+    }
+
+    #[test]
+    fn source_reassigned() {
+        let mut block = Block {
+            instructions: vec![
+                Instruction::Copy {
+                    dest: Var(1),
+                    src: Operand::Var(Var(0)),
+                },
+                Instruction::Read { dest: Var(0) },
+            ],
+            terminator: Terminator::Return(Some(Operand::Var(Var(1)))),
+        };
+        propagate(&mut block);
+
+        assert_eq!(
+            block.terminator,
+            Terminator::Return(Some(Operand::Var(Var(1))))
+        );
+    }
+}
@@ -0,0 +1,268 @@
+//! Removes the blocks which can't be reached and the assignments whose value is never used.
+//!
+//! Which variables are used later is found by a liveness analysis over the control flow graph.
+//! Only instructions without side effects are removed: calls, prints and reads stay, and so does
+//! a division or modulo whose divisor may be `0` or `-1`, because it could raise `SIGFPE`.
+
+use std::collections::HashSet;
+
+use crate::ir::*;
+
+pub fn run(program: &mut Program) {
+    for function in &mut program.functions {
+        remove_unreachable(function);
+        // removing an assignment may make the assignments of its operands dead as well
+        while remove_dead_stores(function) {}
+    }
+}
+
+/// Removes the blocks which can't be reached from the entry and renumbers the others.
+fn remove_unreachable(function: &mut Function) {
+    let mut reached = vec![false; function.blocks.len()];
+    reached[0] = true;
+    let mut worklist = vec![BlockId(0)];
+    while let Some(id) = worklist.pop() {
+        for successor in function.blocks[id.0].terminator.successors() {
+            if !reached[successor.0] {
+                reached[successor.0] = true;
+                worklist.push(successor);
+            }
+        }
+    }
+
+    // the new id of every block which is kept
+    let mut ids = Vec::with_capacity(reached.len());
+    let mut next = 0;
+    for &reached in &reached {
+        ids.push(BlockId(next));
+        next += reached as usize;
+    }
+
+    let blocks = std::mem::take(&mut function.blocks);
+    let mut kept = Vec::with_capacity(next);
+    for (id, mut block) in blocks.into_iter().enumerate() {
+        if !reached[id] {
+            continue;
+        }
+        block.terminator.retarget(|target| ids[target.0]);
+        for instruction in &mut block.instructions {
+            if let Instruction::Phi { args, .. } = instruction {
+                args.retain(|(predecessor, _)| reached[predecessor.0]);
+                for (predecessor, _) in args {
+                    *predecessor = ids[predecessor.0];
+                }
+            }
+        }
+        kept.push(block);
+    }
+    function.blocks = kept;
+}
+
+/// Removes the assignments which aren't used, returns if any has been removed.
+fn remove_dead_stores(function: &mut Function) -> bool {
+    let live_out = liveness(function);
+    let mut removed = false;
+
+    for (block, mut live) in function.blocks.iter_mut().zip(live_out) {
+        add_uses(block.terminator.operands(), &mut live);
+
+        let count = block.instructions.len();
+        let mut instructions = Vec::with_capacity(count);
+        // backwards, so `live` holds the variables used after the instruction
+        for instruction in block.instructions.drain(..).rev() {
+            if let Some(dest) = instruction.dest()
+                && !live.contains(&dest)
+                && is_pure(&instruction)
+            {
+                continue;
+            }
+
+            if let Some(dest) = instruction.dest() {
+                live.remove(&dest);
+            }
+            add_uses(instruction.operands(), &mut live);
+            instructions.push(instruction);
+        }
+        instructions.reverse();
+
+        removed |= instructions.len() < count;
+        block.instructions = instructions;
+    }
+
+    removed
+}
+
+/// Finds the variables which are live at the end of every block.
+fn liveness(function: &Function) -> Vec<HashSet<Var>> {
+    let mut live_in = vec![HashSet::new(); function.blocks.len()];
+    let mut live_out = vec![HashSet::new(); function.blocks.len()];
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        // the analysis goes backwards, so the later blocks are visited first
+        for (id, block) in function.blocks.iter().enumerate().rev() {
+            let mut live = HashSet::new();
+            for successor in block.terminator.successors() {
+                live.extend(live_in[successor.0].iter().copied());
+            }
+            live_out[id] = live.clone();
+
+            add_uses(block.terminator.operands(), &mut live);
+            for instruction in block.instructions.iter().rev() {
+                if let Some(dest) = instruction.dest() {
+                    live.remove(&dest);
+                }
+                add_uses(instruction.operands(), &mut live);
+            }
+
+            if live != live_in[id] {
+                live_in[id] = live;
+                changed = true;
+            }
+        }
+    }
+
+    live_out
+}
+
+fn add_uses(operands: Vec<&Operand>, live: &mut HashSet<Var>) {
+    for operand in operands {
+        if let Operand::Var(var) = operand {
+            live.insert(*var);
+        }
+    }
+}
+
+/// Whether the instruction does nothing besides assigning its destination.
+fn is_pure(instruction: &Instruction) -> bool {
+    match instruction {
+        Instruction::Copy { .. } | Instruction::String { .. } | Instruction::Phi { .. } => true,
+        Instruction::Binary {
+            op: BinOp::Div | BinOp::Mod,
+            right,
+            ..
+        } => matches!(right, Operand::Const(divisor) if *divisor != 0 && *divisor != -1),
+        Instruction::Binary { .. } => true,
+        Instruction::Call { .. } | Instruction::Print { .. } | Instruction::Read { .. } => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eliminate(code: &str, dynamic_checks: bool) -> String {
+        let program = crate::parse(code).unwrap();
+        let mut ir = crate::ir::lower::lower(&program, dynamic_checks);
+        run(&mut ir);
+
+        ir.functions.last().unwrap().to_string()
+    }
+
+    #[test]
+    fn dead_stores() {
+        assert_eq!(
+            eliminate(
+                "int main() { int x = 1; int y = x + 2; string s = \"unused\"; \
+                 y = 3; x = read(); return y; }",
+                false
+            ),
+            "function main() {
+b0:
+    %2 = 3
+    %5 = read
+    return %2
+}
+"
+        );
+    }
+
+    #[test]
+    fn trapping_operations_stay() {
+        assert_eq!(
+            eliminate(
+                "int main() { int a = read(); int b = 7 / a; int c = a % -1; \
+                 int d = a / 2; int e = a % 0; return 0; }",
+                false
+            ),
+            "function main() {
+b0:
+    %0 = read
+    %1 = %0
+    %2 = div 7, %1
+    %4 = sub 0, 1
+    %5 = mod %1, %4
+    %9 = mod %1, 0
+    return 0
+}
+"
+        );
+    }
+
+    #[test]
+    fn unreachable_blocks() {
+        let mut ir = Program {
+            functions: vec![Function {
+                name: "main",
+                params: vec![],
+                blocks: vec![
+                    Block {
+                        instructions: vec![],
+                        terminator: Terminator::Jump(BlockId(2)),
+                    },
+                    Block {
+                        instructions: vec![Instruction::Print {
+                            kind: PrintKind::Int,
+                            value: Operand::Const(1),
+                        }],
+                        terminator: Terminator::Jump(BlockId(2)),
+                    },
+                    Block {
+                        instructions: vec![],
+                        terminator: Terminator::Return(Some(Operand::Const(0))),
+                    },
+                    Block {
+                        instructions: vec![],
+                        terminator: Terminator::Unreachable,
+                    },
+                ],
+                var_count: 0,
+            }],
+        };
+        run(&mut ir);
+
+        assert_eq!(
+            ir.functions[0].to_string(),
+            "function main() {
+b0:
+    jump b1
+b1:
+    return 0
+}
+"
+        );
+    }
+
+    #[test]
+    fn live_across_blocks() {
+        assert_eq!(
+            eliminate(
+                "int f(int p) { int x = p * 2; int y = p * 3; assert(p == 1); return x; }",
+                true
+            ),
+            "function f(%0) {
+b0:
+    %1 = mul %0, 2
+    %2 = %1
+    %5 = eq %0, 1
+    branch %5, b2, b1
+b1:
+    abort
+b2:
+    return %2
+}
+"
+        );
+    }
+}
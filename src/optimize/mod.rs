@@ -0,0 +1,22 @@
+//! The optimisations on the IR, which must not change the observable behaviour of a program.
+//! Operations which may trap have to stay in place even if their value is never used.
+mod constant_folding;
+mod copy_propagation;
+mod dead_code;
+mod strength_reduction;
+mod value_numbering;
+
+use crate::ir::{Program, ssa};
+
+pub type Optimization = fn(&mut Program<'_>);
+
+/// The optimisations in the order they run.
+pub const OPTIMIZATIONS: &[(&str, Optimization)] = &[
+    ("constant_folding", constant_folding::run),
+    ("copy_propagation", copy_propagation::run),
+    ("ssa_construction", ssa::construct),
+    ("value_numbering", value_numbering::run),
+    ("ssa_destruction", ssa::destruct),
+    ("strength_reduction", strength_reduction::run),
+    ("dead_code", dead_code::run),
+];
@@ -0,0 +1,468 @@
+//! Replaces multiplications, divisions and modulos by constants with cheaper operations.
+//!
+//! A multiplication becomes shifts which are added or subtracted, following the binary
+//! representation of the factor or its non-adjacent form, whichever has fewer terms. Factors which
+//! need more than [MAX_TERMS] terms keep the multiplication. A division becomes a multiplication by a magic number which keeps the
+//! upper half of the product, following chapter 10 of Hacker's Delight, and is corrected so it
+//! truncates towards zero like C0. A modulo subtracts the product of that quotient and the
+//! divisor. The divisors `0` and `-1` are left alone, since the division has to trap on them.
+
+use crate::ir::*;
+
+/// The most shifts a multiplication is replaced with. Up to three terms the additions don't take
+/// longer than `imul`, whose latency is three cycles.
+const MAX_TERMS: usize = 3;
+
+pub fn run(program: &mut Program) {
+    for function in &mut program.functions {
+        for id in 0..function.blocks.len() {
+            let instructions = std::mem::take(&mut function.blocks[id].instructions);
+            let mut reducer = Reducer {
+                function,
+                instructions: Vec::with_capacity(instructions.len()),
+            };
+            for instruction in instructions {
+                reducer.reduce(instruction);
+            }
+            function.blocks[id].instructions = reducer.instructions;
+        }
+    }
+}
+
+struct Reducer<'a, 'src> {
+    function: &'a mut Function<'src>,
+    /// The instructions of the block so far.
+    instructions: Vec<Instruction<'src>>,
+}
+
+impl<'src> Reducer<'_, 'src> {
+    fn reduce(&mut self, instruction: Instruction<'src>) {
+        let Instruction::Binary {
+            dest,
+            op,
+            left,
+            right,
+        } = instruction
+        else {
+            self.instructions.push(instruction);
+            return;
+        };
+
+        match (op, left, right) {
+            (BinOp::Mul, value, Operand::Const(factor))
+            | (BinOp::Mul, Operand::Const(factor), value) => self.multiply(dest, value, factor),
+            (BinOp::Div, dividend, Operand::Const(divisor)) if divisor != 0 && divisor != -1 => {
+                self.divide(dest, dividend, divisor)
+            }
+            (BinOp::Mod, dividend, Operand::Const(divisor)) if divisor != 0 && divisor != -1 => {
+                let quotient = self.function.new_var();
+                self.divide(quotient, dividend, divisor);
+                let product = self.function.new_var();
+                self.multiply(product, Operand::Var(quotient), divisor);
+                self.binary(dest, BinOp::Sub, dividend, Operand::Var(product));
+            }
+            _ => self.instructions.push(instruction),
+        }
+    }
+
+    fn multiply(&mut self, dest: Var, value: Operand, factor: i32) {
+        if factor == 0 || factor == 1 {
+            let src = if factor == 0 {
+                Operand::Const(0)
+            } else {
+                value
+            };
+            self.instructions.push(Instruction::Copy { dest, src });
+            return;
+        }
+        let Some(mut terms) = terms(factor) else {
+            self.binary(dest, BinOp::Mul, value, Operand::Const(factor));
+            return;
+        };
+
+        // starting with an added term saves the negation unless every term is subtracted
+        let negate = !terms.iter().any(|&(_, added)| added);
+        if let Some(first) = terms.iter().position(|&(_, added)| added) {
+            let term = terms.remove(first);
+            terms.insert(0, term);
+        }
+
+        let last = terms.len() - 1;
+        let mut sum = None;
+        for (i, &(shift, added)) in terms.iter().enumerate() {
+            let into = (i == last && !negate).then_some(dest);
+            sum = Some(match sum {
+                None => self.shifted(into, value, shift),
+                Some(sum) => {
+                    let term = self.shifted(None, value, shift);
+                    let op = if added != negate {
+                        BinOp::Add
+                    } else {
+                        BinOp::Sub
+                    };
+                    self.operation(into, op, sum, term)
+                }
+            });
+        }
+        if let Some(sum) = sum.filter(|_| negate) {
+            self.binary(dest, BinOp::Sub, Operand::Const(0), sum);
+        }
+    }
+
+    /// Shifts the value left, into `dest` or a new variable.
+    fn shifted(&mut self, dest: Option<Var>, value: Operand, shift: u32) -> Operand {
+        match (dest, shift) {
+            (None, 0) => value,
+            _ => self.operation(dest, BinOp::Shl, value, Operand::Const(shift as i32)),
+        }
+    }
+
+    /// Computes the operation into `dest` or a new variable.
+    fn operation(
+        &mut self,
+        dest: Option<Var>,
+        op: BinOp,
+        left: Operand,
+        right: Operand,
+    ) -> Operand {
+        match dest {
+            Some(dest) => {
+                self.binary(dest, op, left, right);
+                Operand::Var(dest)
+            }
+            None => self.temporary(op, left, right),
+        }
+    }
+
+    /// Divides by a constant which is neither `0` nor `-1`.
+    fn divide(&mut self, dest: Var, dividend: Operand, divisor: i32) {
+        let magnitude = divisor.unsigned_abs();
+
+        if divisor == 1 {
+            self.instructions.push(Instruction::Copy {
+                dest,
+                src: dividend,
+            });
+        } else if magnitude.is_power_of_two() {
+            // a negative dividend is rounded up by adding `2^k - 1` before the shift
+            let k = magnitude.trailing_zeros() as i32;
+            let sign = match k {
+                1 => dividend,
+                _ => self.temporary(BinOp::Sar, dividend, Operand::Const(k - 1)),
+            };
+            let bias = self.temporary(BinOp::Shr, sign, Operand::Const(32 - k));
+            let biased = self.temporary(BinOp::Add, dividend, bias);
+            if divisor > 0 {
+                self.binary(dest, BinOp::Sar, biased, Operand::Const(k));
+            } else {
+                let quotient = self.temporary(BinOp::Sar, biased, Operand::Const(k));
+                self.binary(dest, BinOp::Sub, Operand::Const(0), quotient);
+            }
+        } else {
+            let (magic, shift) = magic(divisor);
+            let mut quotient = self.temporary(BinOp::MulHigh, dividend, Operand::Const(magic));
+            if divisor > 0 && magic < 0 {
+                quotient = self.temporary(BinOp::Add, quotient, dividend);
+            } else if divisor < 0 && magic > 0 {
+                quotient = self.temporary(BinOp::Sub, quotient, dividend);
+            }
+            if shift > 0 {
+                quotient = self.temporary(BinOp::Sar, quotient, Operand::Const(shift));
+            }
+            // a negative quotient is one too small, so its sign bit is added
+            let sign = self.temporary(BinOp::Shr, quotient, Operand::Const(31));
+            self.binary(dest, BinOp::Add, quotient, sign);
+        }
+    }
+
+    fn binary(&mut self, dest: Var, op: BinOp, left: Operand, right: Operand) {
+        self.instructions.push(Instruction::Binary {
+            dest,
+            op,
+            left,
+            right,
+        });
+    }
+
+    /// Computes the operation into a new variable.
+    fn temporary(&mut self, op: BinOp, left: Operand, right: Operand) -> Operand {
+        let dest = self.function.new_var();
+        self.binary(dest, op, left, right);
+        Operand::Var(dest)
+    }
+}
+
+/// The powers of two whose sum or difference is the factor, as their exponents and whether
+/// they're added, from the largest one. `None` if there are more than [MAX_TERMS] of them.
+fn terms(factor: i32) -> Option<Vec<(u32, bool)>> {
+    let magnitude = factor.unsigned_abs();
+    let binary: Vec<_> = (0..32)
+        .rev()
+        .filter(|bit| magnitude >> bit & 1 == 1)
+        .map(|bit| (bit, true))
+        .collect();
+
+    // the non-adjacent form has a nonzero digit at most every other bit
+    let mut non_adjacent = Vec::new();
+    let mut rest = magnitude as u64;
+    let mut bit = 0;
+    while rest != 0 {
+        if rest & 1 == 1 {
+            let added = rest & 3 == 1;
+            // a term of `2^32` vanishes in 32 bits
+            if bit < 32 {
+                non_adjacent.push((bit, added));
+            }
+            rest = if added { rest - 1 } else { rest + 1 };
+        }
+        rest >>= 1;
+        bit += 1;
+    }
+    non_adjacent.reverse();
+
+    let terms = match non_adjacent.len() < binary.len() {
+        true => non_adjacent,
+        false => binary,
+    };
+    (terms.len() <= MAX_TERMS).then(|| {
+        terms
+            .into_iter()
+            .map(|(bit, added)| (bit, added == (factor > 0)))
+            .collect()
+    })
+}
+
+/// The magic number and the shift to divide by the divisor, whose absolute value is at least 2
+/// and not a power of two (Hacker's Delight, figure 10-1).
+fn magic(divisor: i32) -> (i32, i32) {
+    const TWO_31: u32 = 1 << 31;
+
+    let magnitude = divisor.unsigned_abs();
+    let t = TWO_31 + ((divisor as u32) >> 31);
+    // the largest dividend whose remainder is `magnitude - 1`
+    let anc = t - 1 - t % magnitude;
+
+    let mut p = 31;
+    let (mut q1, mut r1) = (TWO_31 / anc, TWO_31 % anc);
+    let (mut q2, mut r2) = (TWO_31 / magnitude, TWO_31 % magnitude);
+    loop {
+        p += 1;
+        q1 = q1.wrapping_mul(2);
+        r1 = r1.wrapping_mul(2);
+        if r1 >= anc {
+            q1 = q1.wrapping_add(1);
+            r1 -= anc;
+        }
+        q2 = q2.wrapping_mul(2);
+        r2 = r2.wrapping_mul(2);
+        if r2 >= magnitude {
+            q2 = q2.wrapping_add(1);
+            r2 -= magnitude;
+        }
+
+        let delta = magnitude - r2;
+        if q1 > delta || (q1 == delta && r1 != 0) {
+            break;
+        }
+    }
+
+    let magic = q2.wrapping_add(1) as i32;
+    match divisor < 0 {
+        true => (magic.wrapping_neg(), p - 32),
+        false => (magic, p - 32),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Reduces `%1 = op %0, right` and returns the instructions.
+    fn reduce(op: BinOp, right: i32) -> Vec<Instruction<'static>> {
+        let mut program = Program {
+            functions: vec![Function {
+                name: "f",
+                params: vec![Var(0)],
+                blocks: vec![Block {
+                    instructions: vec![Instruction::Binary {
+                        dest: Var(1),
+                        op,
+                        left: Operand::Var(Var(0)),
+                        right: Operand::Const(right),
+                    }],
+                    terminator: Terminator::Return(Some(Operand::Var(Var(1)))),
+                }],
+                var_count: 2,
+            }],
+        };
+        run(&mut program);
+
+        program.functions.remove(0).blocks.remove(0).instructions
+    }
+
+    /// Runs the instructions with `%0 = value` and returns `%1`, `None` if an operation traps.
+    fn evaluate(instructions: &[Instruction], value: i32) -> Option<i32> {
+        let mut vars = HashMap::from([(Var(0), value)]);
+        let operand = |vars: &HashMap<Var, i32>, operand: &Operand| match operand {
+            Operand::Const(value) => *value,
+            Operand::Var(var) => vars[var],
+        };
+
+        for instruction in instructions {
+            let (dest, value) = match instruction {
+                Instruction::Copy { dest, src } => (*dest, operand(&vars, src)),
+                Instruction::Binary {
+                    dest,
+                    op,
+                    left,
+                    right,
+                } => (*dest, op.eval(operand(&vars, left), operand(&vars, right))?),
+                _ => unreachable!("only arithmetic is generated"),
+            };
+            vars.insert(dest, value);
+        }
+
+        Some(vars[&Var(1)])
+    }
+
+    fn to_strings(instructions: &[Instruction]) -> Vec<String> {
+        instructions.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn same_results() {
+        let mut constants: Vec<i32> = (-300..=300).collect();
+        for k in 8..31 {
+            constants.extend([1 << k, -(1 << k), (1 << k) + 1, (1 << k) - 1, -(1 << k) - 1]);
+        }
+        constants.extend([
+            i32::MIN,
+            i32::MIN + 1,
+            i32::MAX,
+            641,
+            6700417,
+            -1_000_000_007,
+        ]);
+        let values = [
+            i32::MIN,
+            i32::MIN + 1,
+            i32::MAX,
+            i32::MAX - 1,
+            -1,
+            0,
+            1,
+            7,
+            -7,
+            100,
+            -100,
+            65535,
+            -65536,
+            123_456_789,
+            -987_654_321,
+        ];
+
+        for op in [BinOp::Mul, BinOp::Div, BinOp::Mod] {
+            for &constant in &constants {
+                let instructions = reduce(op, constant);
+                for value in values
+                    .into_iter()
+                    .chain([constant, constant.wrapping_neg()])
+                {
+                    assert_eq!(
+                        evaluate(&instructions, value),
+                        op.eval(value, constant),
+                        "{:?} {}, {}",
+                        op,
+                        value,
+                        constant
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn multiplication() {
+        assert_eq!(to_strings(&reduce(BinOp::Mul, 8)), ["%1 = shl %0, 3"]);
+        assert_eq!(
+            to_strings(&reduce(BinOp::Mul, 9)),
+            ["%2 = shl %0, 3", "%1 = add %2, %0"]
+        );
+        assert_eq!(
+            to_strings(&reduce(BinOp::Mul, -7)),
+            ["%2 = shl %0, 3", "%1 = sub %0, %2"]
+        );
+        assert_eq!(
+            to_strings(&reduce(BinOp::Mul, 10)),
+            ["%2 = shl %0, 3", "%3 = shl %0, 1", "%1 = add %2, %3"]
+        );
+        assert_eq!(
+            to_strings(&reduce(BinOp::Mul, -30)),
+            ["%2 = shl %0, 1", "%3 = shl %0, 5", "%1 = sub %2, %3"]
+        );
+        assert_eq!(
+            to_strings(&reduce(BinOp::Mul, -10)),
+            [
+                "%2 = shl %0, 3",
+                "%3 = shl %0, 1",
+                "%4 = add %2, %3",
+                "%1 = sub 0, %4"
+            ]
+        );
+        // 85 is `1010101` in both forms
+        assert_eq!(to_strings(&reduce(BinOp::Mul, 85)), ["%1 = mul %0, 85"]);
+    }
+
+    #[test]
+    fn division() {
+        assert_eq!(
+            to_strings(&reduce(BinOp::Div, 4)),
+            [
+                "%2 = sar %0, 1",
+                "%3 = shr %2, 30",
+                "%4 = add %0, %3",
+                "%1 = sar %4, 2"
+            ]
+        );
+        assert_eq!(
+            to_strings(&reduce(BinOp::Div, 7)),
+            [
+                "%2 = mulhi %0, -1840700269",
+                "%3 = add %2, %0",
+                "%4 = sar %3, 2",
+                "%5 = shr %4, 31",
+                "%1 = add %4, %5"
+            ]
+        );
+        assert_eq!(
+            to_strings(&reduce(BinOp::Mod, 3)),
+            [
+                "%3 = mulhi %0, 1431655766",
+                "%4 = shr %3, 31",
+                "%2 = add %3, %4",
+                "%6 = shl %2, 1",
+                "%5 = add %6, %2",
+                "%1 = sub %0, %5"
+            ]
+        );
+    }
+
+    #[test]
+    fn trapping_divisors_stay() {
+        for op in [BinOp::Div, BinOp::Mod] {
+            for divisor in [0, -1] {
+                assert_eq!(
+                    reduce(op, divisor),
+                    [Instruction::Binary {
+                        dest: Var(1),
+                        op,
+                        left: Operand::Var(Var(0)),
+                        right: Operand::Const(divisor),
+                    }]
+                );
+            }
+        }
+    }
+}
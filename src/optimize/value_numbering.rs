@@ -0,0 +1,233 @@
+//! Reuses the values which have already been computed, which has to run in SSA form.
+//!
+//! The blocks are visited in a walk over the dominator tree, so an operation is only replaced by
+//! one in a block which dominates it. Every variable which is a copy of another operand or holds
+//! the same value as an earlier operation is replaced by that operand everywhere, which is valid
+//! because every variable is only assigned once. A division or modulo is only replaced by an
+//! earlier one with the same operands, which would have trapped already, and the earlier one
+//! stays in place.
+
+use std::collections::HashMap;
+
+use crate::ir::{dominance::Dominators, *};
+
+pub fn run(program: &mut Program) {
+    for function in &mut program.functions {
+        number(function);
+    }
+}
+
+/// An operation with its operands replaced by the operands holding the same values, ordered if
+/// the operation is commutative.
+type Expression = (BinOp, Operand, Operand);
+
+fn number(function: &mut Function) {
+    let dominators = Dominators::new(function);
+    let children = dominators.children();
+    // the operand every variable holds the same value as
+    let mut values: HashMap<Var, Operand> = HashMap::new();
+    // the variables holding the values of the operations in the dominating blocks
+    let mut expressions: HashMap<Expression, Var> = HashMap::new();
+
+    // a block is left again after the blocks it dominates, which forgets its operations
+    enum Visit {
+        Enter(BlockId),
+        Leave(Vec<Expression>),
+    }
+    let mut visits = vec![Visit::Enter(BlockId(0))];
+
+    while let Some(visit) = visits.pop() {
+        let id = match visit {
+            Visit::Enter(id) => id,
+            Visit::Leave(computed) => {
+                for expression in computed {
+                    expressions.remove(&expression);
+                }
+                continue;
+            }
+        };
+
+        let mut computed = Vec::new();
+        for instruction in &mut function.blocks[id.0].instructions {
+            for operand in instruction.operands_mut() {
+                replace(operand, &values);
+            }
+
+            match instruction {
+                Instruction::Copy { dest, src } => {
+                    values.insert(*dest, *src);
+                }
+                Instruction::Binary {
+                    dest,
+                    op,
+                    left,
+                    right,
+                } => {
+                    let expression = match op.is_commutative() && left > right {
+                        true => (*op, *right, *left),
+                        false => (*op, *left, *right),
+                    };
+                    match expressions.get(&expression) {
+                        Some(var) => {
+                            values.insert(*dest, Operand::Var(*var));
+                            *instruction = Instruction::Copy {
+                                dest: *dest,
+                                src: Operand::Var(*var),
+                            };
+                        }
+                        None => {
+                            expressions.insert(expression, *dest);
+                            computed.push(expression);
+                        }
+                    }
+                }
+                // a phi whose arguments are all the same, besides the phi itself, is a copy
+                Instruction::Phi { dest, args } => {
+                    let mut others = args
+                        .iter()
+                        .map(|(_, arg)| *arg)
+                        .filter(|arg| *arg != Operand::Var(*dest));
+                    if let Some(first) = others.next()
+                        && others.all(|arg| arg == first)
+                    {
+                        values.insert(*dest, first);
+                    }
+                }
+                Instruction::String { .. }
+                | Instruction::Call { .. }
+                | Instruction::Print { .. }
+                | Instruction::Read { .. } => {}
+            }
+        }
+
+        visits.push(Visit::Leave(computed));
+        for &child in children[id.0].iter().rev() {
+            visits.push(Visit::Enter(child));
+        }
+    }
+
+    // the arguments of phis may come from blocks which are visited later
+    for block in &mut function.blocks {
+        let operands = block
+            .instructions
+            .iter_mut()
+            .flat_map(Instruction::operands_mut);
+        for operand in operands.chain(block.terminator.operands_mut()) {
+            replace(operand, &values);
+        }
+    }
+}
+
+/// Replaces the variable by the operand it's a copy of, following chains of copies.
+fn replace(operand: &mut Operand, values: &HashMap<Var, Operand>) {
+    while let Operand::Var(var) = operand
+        && let Some(value) = values.get(var)
+    {
+        *operand = *value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(code: &str, dynamic_checks: bool) -> String {
+        let program = crate::parse(code).unwrap();
+        let mut ir = lower::lower(&program, dynamic_checks);
+        ssa::construct(&mut ir);
+        run(&mut ir);
+
+        ir.functions.last().unwrap().to_string()
+    }
+
+    #[test]
+    fn common_subexpressions() {
+        assert_eq!(
+            number(
+                "int f(int a, int b) { return (a * b) + (b * a) - a * b; }",
+                false
+            ),
+            "function f(%0, %1) {
+b0:
+    %7 = mul %0, %1
+    %8 = %7
+    %9 = add %7, %7
+    %10 = %7
+    %11 = sub %9, %7
+    return %11
+b1:
+    unreachable
+}
+"
+        );
+    }
+
+    #[test]
+    fn trapping_operations() {
+        assert_eq!(
+            number(
+                "int f(int a, int b) { int x = a / b; int y = a / b; int z = b / a; \
+                 return x + y + z; }",
+                false
+            ),
+            "function f(%0, %1) {
+b0:
+    %10 = div %0, %1
+    %11 = %10
+    %12 = %10
+    %13 = %10
+    %14 = div %1, %0
+    %15 = %14
+    %16 = add %10, %10
+    %17 = add %16, %14
+    return %17
+b1:
+    unreachable
+}
+"
+        );
+    }
+
+    #[test]
+    fn only_dominating_blocks() {
+        let mut ir = Program {
+            functions: vec![Function {
+                name: "f",
+                params: vec![Var(0)],
+                blocks: vec![
+                    Block {
+                        instructions: vec![],
+                        terminator: Terminator::Branch {
+                            cond: Operand::Var(Var(0)),
+                            then: BlockId(1),
+                            otherwise: BlockId(2),
+                        },
+                    },
+                    Block {
+                        instructions: vec![Instruction::Binary {
+                            dest: Var(1),
+                            op: BinOp::Add,
+                            left: Operand::Var(Var(0)),
+                            right: Operand::Const(1),
+                        }],
+                        terminator: Terminator::Jump(BlockId(2)),
+                    },
+                    Block {
+                        instructions: vec![Instruction::Binary {
+                            dest: Var(2),
+                            op: BinOp::Add,
+                            left: Operand::Const(1),
+                            right: Operand::Var(Var(0)),
+                        }],
+                        terminator: Terminator::Return(Some(Operand::Var(Var(2)))),
+                    },
+                ],
+                var_count: 3,
+            }],
+        };
+        let before = ir.clone();
+        run(&mut ir);
+
+        assert_eq!(ir, before);
+    }
+}
@@ -1,147 +1,241 @@
-use crate::grammar::*;
-use chumsky::{Parser, prelude::*};
-
-type ParseError<'src> = extra::Err<Rich<'src, char>>;
+use crate::{
+    grammar::*,
+    lexer::{Spanned, Token},
+};
+use chumsky::{
+    Parser,
+    input::{MappedInput, Stream},
+    prelude::*,
+};
+
+/// The tokens of the lexer as input for the parser.
+type Tokens<'src> = MappedInput<
+    Token<'src>,
+    SimpleSpan,
+    Stream<std::vec::IntoIter<Spanned<Token<'src>>>>,
+    fn(Spanned<Token<'src>>) -> Spanned<Token<'src>>,
+>;
+
+type ParseError<'src> = extra::Err<Rich<'src, Token<'src>>>;
 
 macro_rules! parser {
     ($lifetime: lifetime, $node: ty) => {
-        impl Parser<$lifetime, &$lifetime str, $node, ParseError<$lifetime>>
+        impl Parser<$lifetime, Tokens<$lifetime>, $node, ParseError<$lifetime>> + Clone
     }
 }
 
-// impl Parser<'src, &'src str, Statements<'src>, ParseError<'src>>;
-
-const KEYWORDS: &[&str] = &[
-    "struct",
-    "if",
-    "else",
-    "while",
-    "for",
-    "continue",
-    "break",
-    "return",
-    "assert",
-    "true",
-    "false",
-    "NULL",
-    "print",
-    "read",
-    "alloc",
-    "alloc_array",
-    "int",
-    "bool",
-    "void",
-    "char",
-    "string",
-];
-
-fn padding_at_least_once<'src>() -> parser!('src, ()) {
-    one_of(" \n\t\r")
-        .ignored()
-        .or(comment_parser())
+/// Parses the tokens of the code with the given length.
+/// Broken statements and functions are skipped, so there may be a recovered program in spite of
+/// parsing errors. The tokens of the errors are turned into strings to report them like the
+/// errors of the lexer.
+pub fn parse_tokens<'src>(
+    tokens: Vec<Spanned<Token<'src>>>,
+    len: usize,
+) -> (Option<Program<'src>>, Vec<Rich<'src, String>>) {
+    let (program, errors) = parser()
+        .parse(tokens_input(tokens, len))
+        .into_output_errors();
+    let errors = errors.into_iter().map(|e| e.map_token(|t| t.to_string()));
+
+    (program, errors.collect())
+}
+
+/// `len` is the length of the code to point at its end if the tokens run out.
+fn tokens_input(tokens: Vec<Spanned<Token<'_>>>, len: usize) -> Tokens<'_> {
+    Stream::from_iter(tokens).map((len..len).into(), |spanned| spanned)
+}
+
+fn parser<'src>() -> parser!('src, Program<'src>) {
+    // a broken function is skipped up to and including its body
+    let skip_function = none_of([Token::Ctrl('{')])
+        .repeated()
+        .then(delimited_group())
+        .to(None);
+
+    function_parser()
+        .map(Some)
+        .recover_with(via_parser(skip_function))
         .repeated()
         .at_least(1)
+        .collect::<Vec<_>>()
+        .then_ignore(end())
+        .map(|functions| Program(functions.into_iter().flatten().collect()))
 }
 
-fn padding<'src>() -> parser!('src, ()) {
-    one_of(" \n\t\r")
-        .ignored()
-        .or(comment_parser())
-        .repeated()
-        .ignored()
+fn function_parser<'src>() -> parser!('src, Function<'src>) {
+    let params = param_parser()
+        .separated_by(just(Token::Ctrl(',')))
+        .collect::<Vec<_>>()
+        .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')));
+
+    type_parser()
+        .then(ident_parser())
+        .then(params)
+        .then(annotations_parser())
+        .then_ignore(just(Token::Ctrl('{')))
+        .then(statements_parser())
+        .then_ignore(just(Token::Ctrl('}')))
+        .map(
+            |((((return_type, ident), params), annotations), statements)| Function {
+                return_type,
+                ident,
+                params,
+                annotations,
+                statements,
+            },
+        )
 }
 
-pub fn parse<'src>(code: &'src str) -> ParseResult<Program<'src>, Rich<'src, char>> {
-    parser().parse(code)
+fn param_parser<'src>() -> parser!('src, Param<'src>) {
+    type_parser()
+        .then(ident_parser())
+        .map(|(ty, ident)| Param { ty, ident })
 }
 
-fn parser<'src>() -> parser!('src, Program<'src>) {
-    padding()
-        .then_ignore(just("int"))
-        .then_ignore(padding_at_least_once())
-        .then_ignore(just("main"))
-        .then_ignore(just("(").padded_by(padding()))
-        .then_ignore(just(")").padded_by(padding()))
-        .then_ignore(just("{").padded_by(padding()))
-        .then(statements_parser())
-        .then_ignore(just("}").padded_by(padding()))
-        .then_ignore(end().padded_by(padding()))
-        .map(|(_main_type, statements)| Program(statements))
+/// All annotations in front of a function body or a statement.
+fn annotations_parser<'src>() -> parser!('src, Vec<Annotation<'src>>) {
+    annotation_parser()
+        .repeated()
+        .at_least(1)
+        .collect::<Vec<_>>()
+        .delimited_by(just(Token::AnnotationStart), just(Token::AnnotationEnd))
+        .repeated()
+        .collect::<Vec<_>>()
+        .map(|annotations| annotations.concat())
 }
 
-fn block_comment<'src>() -> parser!('src, ()) {
-    recursive(|block_comment| {
-        let block_comment_body = any()
-            .and_is(just("/*").not())
-            .and_is(just("*/").not())
-            .ignored();
-
-        just("/*")
-            .ignored()
-            .then_ignore(block_comment_body.or(block_comment).repeated())
-            .then_ignore(just("*/"))
-    })
+fn annotation_parser<'src>() -> parser!('src, Annotation<'src>) {
+    let annotation = select! {
+        Token::Ident("requires") => Annotation::Requires as fn(_) -> _,
+        Token::Ident("ensures") => Annotation::Ensures as fn(_) -> _,
+        Token::Ident("loop_invariant") => Annotation::LoopInvariant as fn(_) -> _,
+        Token::Keyword("assert") => Annotation::Assert as fn(_) -> _,
+    };
+
+    annotation
+        .then(exp_parser())
+        .then_ignore(just(Token::Ctrl(';')))
+        .map(|(annotation, exp)| annotation(exp))
 }
 
-fn line_comments<'src>() -> parser!('src, ()) {
-    just("//")
-        .ignored()
-        .then_ignore(any().and_is(just("\n").not()).repeated())
+fn statements_parser<'src>() -> parser!('src, Vec<Statement<'src>>) {
+    statement_parser()
+        .map(Some)
+        .recover_with(via_parser(skip_statement().to(None)))
+        .repeated()
+        .collect::<Vec<_>>()
+        // broken statements are dropped
+        .map(|statements| statements.into_iter().flatten().collect())
 }
 
-fn comment_parser<'src>() -> parser!('src, ()) {
-    choice((block_comment(), line_comments()))
+/// Skips a broken statement up to and including its `;` or up to the `}` of the block.
+fn skip_statement<'src>() -> parser!('src, ()) {
+    let semicolon = just(Token::Ctrl(';')).ignored();
+
+    // annotations are statements of their own
+    let annotation = none_of([Token::AnnotationEnd])
+        .repeated()
+        .delimited_by(just(Token::AnnotationStart), just(Token::AnnotationEnd));
+
+    // unbalanced delimiters are skipped like any other token
+    let token = choice((
+        delimited_group(),
+        none_of([Token::Ctrl(';'), Token::Ctrl('}')]).ignored(),
+    ))
+    .and_is(just(Token::AnnotationStart).not());
+
+    choice((
+        annotation,
+        token
+            .repeated()
+            .at_least(1)
+            .then(semicolon.clone().or_not())
+            .ignored(),
+        semicolon,
+    ))
 }
 
-fn statements_parser<'src>() -> parser!('src, Statements<'src>) {
-    recursive(|statements_parser| {
-        let none = empty().padded_by(padding().boxed()).to(Statements::None);
-        let statements =
-            statement_parser()
-                .boxed()
-                .then(statements_parser)
-                .map(|(statement, next)| Statements::Statement {
-                    current: statement,
-                    next: Box::new(next),
-                });
+/// Balanced parentheses, braces or annotation delimiters with everything in between, so that a
+/// `;` or `}` in there doesn't stop the recovery too early.
+fn delimited_group<'src>() -> parser!('src, ()) {
+    recursive(|group| {
+        let content = choice((
+            group,
+            none_of([
+                Token::Ctrl('('),
+                Token::Ctrl(')'),
+                Token::Ctrl('{'),
+                Token::Ctrl('}'),
+                Token::AnnotationStart,
+                Token::AnnotationEnd,
+            ])
+            .ignored(),
+        ))
+        .repeated();
 
-        choice((statements, none))
+        choice((
+            content
+                .clone()
+                .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')'))),
+            content
+                .clone()
+                .delimited_by(just(Token::Ctrl('{')), just(Token::Ctrl('}'))),
+            content.delimited_by(just(Token::AnnotationStart), just(Token::AnnotationEnd)),
+        ))
     })
 }
 
 fn statement_parser<'src>() -> parser!('src, Statement<'src>) {
+    let semicolon = just(Token::Ctrl(';'));
+    let parenthesized =
+        || exp_parser().delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')));
+
     let decl = decl_parser()
-        .then_ignore(just(';'))
-        .map(|decl| Statement::Decl(decl));
+        .then_ignore(semicolon.clone())
+        .map(Statement::Decl);
     let simp = simp_parser()
-        .then_ignore(just(';'))
-        .map(|simp| Statement::Simp(simp));
-    let ret = just("return")
-        .ignored()
-        .padded_by(padding())
-        .then(exp_parser())
-        .then_ignore(just(';').padded_by(padding()))
-        .map(|(_, exp)| Statement::Return(exp));
-
-    choice((decl, simp, ret)).padded_by(padding())
+        .then_ignore(semicolon.clone())
+        .map(Statement::Simp);
+    let ret = just(Token::Keyword("return"))
+        .ignore_then(exp_parser().or_not())
+        .then_ignore(semicolon.clone())
+        .map(Statement::Return);
+    let assert = just(Token::Keyword("assert"))
+        .ignore_then(parenthesized())
+        .then_ignore(semicolon.clone())
+        .map(Statement::Assert);
+    let print = just(Token::Keyword("print"))
+        .ignore_then(parenthesized())
+        .then_ignore(semicolon.clone())
+        .map(Statement::Print);
+
+    let annotations = annotations_parser()
+        .filter(|annotations| !annotations.is_empty())
+        .map(Statement::Annotations);
+
+    let exp = exp_parser().then_ignore(semicolon).map(Statement::Exp);
+
+    choice((decl, simp, ret, assert, print, annotations, exp))
 }
 
 fn decl_parser<'src>() -> parser!('src, Declaration<'src>) {
-    let init = padding()
-        .ignore_then(just("int"))
-        .ignore_then(padding_at_least_once())
+    type_parser()
         .then(ident_parser())
-        .then_ignore(just("=").padded_by(padding()))
-        .then(exp_parser())
-        .map(|((_, ident), exp)| Declaration::IdentExp { ident, exp });
-
-    let decl = padding()
-        .ignore_then(just("int"))
-        .ignore_then(padding_at_least_once())
-        .then(ident_parser())
-        .map(|(_, ident)| Declaration::Ident(ident));
+        .then(just(Token::Op("=")).ignore_then(exp_parser()).or_not())
+        .map(|((ty, ident), exp)| match exp {
+            Some(exp) => Declaration::IdentExp { ty, ident, exp },
+            None => Declaration::Ident { ty, ident },
+        })
+}
 
-    choice((init, decl)).padded_by(padding())
+fn type_parser<'src>() -> parser!('src, Type) {
+    select! {
+        Token::Keyword("int") => Type::Int,
+        Token::Keyword("bool") => Type::Bool,
+        Token::Keyword("char") => Type::Char,
+        Token::Keyword("string") => Type::String,
+        Token::Keyword("void") => Type::Void,
+    }
 }
 
 fn simp_parser<'src>() -> parser!('src, SimpleInstruction<'src>) {
@@ -153,278 +247,351 @@ fn simp_parser<'src>() -> parser!('src, SimpleInstruction<'src>) {
 
 fn lvalue_parser<'src>() -> parser!('src, LValue<'src>) {
     recursive(|lvalue_parser| {
-        let ident = ident_parser()
-            .boxed()
-            .padded_by(padding().boxed())
-            .map(|ident| LValue::Ident(ident));
-
-        let lvalue = just('(')
-            .ignored()
-            .padded_by(padding().boxed())
-            .then(lvalue_parser.clone())
-            .then_ignore(just(')').padded_by(padding().boxed()))
-            .map(|(_, lvalue)| LValue::LValue(Box::new(lvalue)));
-
-        choice((ident, lvalue)).padded_by(padding().boxed())
+        let ident = ident_parser().map(LValue::Ident);
+
+        let lvalue = lvalue_parser
+            .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')))
+            .map(|lvalue| LValue::LValue(Box::new(lvalue)));
+
+        choice((ident, lvalue))
     })
 }
 
 fn exp_parser<'src>() -> parser!('src, Expression<'src>) {
     recursive(|exp_parser| {
-        let nested_exp = just('(')
-            .ignored()
-            .padded_by(padding().boxed())
-            .then(exp_parser.clone())
-            .then_ignore(just(')').padded_by(padding().boxed()))
-            .map(|(_, e)| Expression::NestedExp(Box::new(e)));
-        let intconst = intconst_parser()
-            .boxed()
-            .padded_by(padding().boxed())
-            .map(|i| Expression::Intconst(i));
-        let ident = ident_parser()
-            .boxed()
-            .padded_by(padding().boxed())
-            .map(|ident| Expression::Ident(ident));
-
-        let prec4 = choice((nested_exp.clone(), intconst.clone(), ident.clone()));
+        let parenthesized = exp_parser
+            .clone()
+            .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')')));
+
+        let nested_exp = parenthesized
+            .clone()
+            .map(|e| Expression::NestedExp(Box::new(e)));
+        let intconst = intconst_parser().map(Expression::Intconst);
+        let boolconst = select! {
+            Token::Keyword("true") => Expression::Boolconst(true),
+            Token::Keyword("false") => Expression::Boolconst(false),
+        };
+        let charconst = select! { Token::Charconst(c) => Expression::Charconst(Charconst(c)) };
+        let stringconst =
+            select! { Token::Stringconst(s) => Expression::Stringconst(Stringconst(s)) };
+        let call = ident_parser()
+            .then(
+                exp_parser
+                    .clone()
+                    .separated_by(just(Token::Ctrl(',')))
+                    .collect::<Vec<_>>()
+                    .delimited_by(just(Token::Ctrl('(')), just(Token::Ctrl(')'))),
+            )
+            .map(|(ident, args)| Expression::Call { ident, args });
+        let ident = ident_parser().map(Expression::Ident);
+        let read = just(Token::Keyword("read"))
+            .then(just(Token::Ctrl('(')))
+            .then(just(Token::Ctrl(')')))
+            .to(Expression::Read);
+        let result = just(Token::Result).to(Expression::Result);
+        let length = just(Token::Length)
+            .ignore_then(parenthesized)
+            .map(|exp| Expression::Length(Box::new(exp)));
+
+        let prec4 = choice((
+            nested_exp,
+            intconst,
+            boolconst,
+            charconst,
+            stringconst,
+            call,
+            ident,
+            read,
+            result,
+            length,
+        ))
+        .boxed();
 
         // - (unary)
         let prec3 = unop_parser()
-            .boxed()
             .repeated()
-            .foldr(prec4.clone(), |op, exp| Expression::Unop {
+            .foldr(prec4, |op, exp| Expression::Unop {
                 op,
                 right: Box::new(exp),
-            });
+            })
+            .boxed();
 
         // "*", "/", "%"
         let prec2 = {
-            let op = {
-                let mul = just("*").to(BinOperation::Multiplication);
-                let div = just("/").to(BinOperation::Division);
-                let modulo = just("%").to(BinOperation::Mod);
-
-                choice((mul, div, modulo))
+            let op = select! {
+                Token::Op("*") => BinOperation::Multiplication,
+                Token::Op("/") => BinOperation::Division,
+                Token::Op("%") => BinOperation::Mod,
             };
 
             prec3
                 .clone()
-                .foldl(op.then(prec3.clone()).repeated(), |left, (op, right)| {
+                .foldl(op.then(prec3).repeated(), |left, (op, right)| {
                     Expression::Binop {
                         left: Box::new(left),
                         op,
                         right: Box::new(right),
                     }
                 })
+                .boxed()
         };
 
         // "+", "-"
         let prec1 = {
-            let plus = just("+").to(BinOperation::Plus);
-            let minus = just("-").to(BinOperation::Minus);
+            let op = select! {
+                Token::Op("+") => BinOperation::Plus,
+                Token::Op("-") => BinOperation::Minus,
+            };
 
-            prec2.clone().foldl(
-                choice((plus, minus)).then(prec2.clone()).repeated(),
-                |left, (op, right)| Expression::Binop {
-                    left: Box::new(left),
-                    op,
-                    right: Box::new(right),
-                },
-            )
+            prec2
+                .clone()
+                .foldl(op.then(prec2).repeated(), |left, (op, right)| {
+                    Expression::Binop {
+                        left: Box::new(left),
+                        op,
+                        right: Box::new(right),
+                    }
+                })
+                .boxed()
+        };
+
+        // "==", "!="
+        let op = select! {
+            Token::Op("==") => BinOperation::Equal,
+            Token::Op("!=") => BinOperation::NotEqual,
         };
 
         prec1
+            .clone()
+            .foldl(op.then(prec1).repeated(), |left, (op, right)| {
+                Expression::Binop {
+                    left: Box::new(left),
+                    op,
+                    right: Box::new(right),
+                }
+            })
     })
 }
 
 fn intconst_parser<'src>() -> parser!('src, Intconst<'src>) {
-    let dec = decnum_parser().map(|d| Intconst::Decnum(d));
-    let hex = hexnum_parser().map(|h| Intconst::Hexnum(h));
-
-    choice((hex, dec)).padded_by(padding())
+    select! {
+        Token::Decnum(decnum) => Intconst::Decnum(Decnum(decnum)),
+        Token::Hexnum(hexnum) => Intconst::Hexnum(Hexnum(hexnum)),
+    }
 }
 
-fn unop_parser<'src>() -> impl Parser<'src, &'src str, UnOperation, ParseError<'src>> {
-    just('-').to(UnOperation::Minus).padded_by(padding())
+fn unop_parser<'src>() -> parser!('src, UnOperation) {
+    just(Token::Op("-")).to(UnOperation::Minus)
 }
 
 fn asnop_parser<'src>() -> parser!('src, AsNop) {
-    let equal = just("=").to(AsNop::Equal);
-    let plus = just("+=").to(AsNop::PlusEqual);
-    let minus = just("-=").to(AsNop::MinusEqual);
-    let mul = just("*=").to(AsNop::MultEqual);
-    let div = just("/=").to(AsNop::DivEqual);
-    let r#mod = just("%=").to(AsNop::ModEqual);
-
-    choice((equal, plus, minus, mul, div, r#mod)).padded()
+    select! {
+        Token::Op("=") => AsNop::Equal,
+        Token::Op("+=") => AsNop::PlusEqual,
+        Token::Op("-=") => AsNop::MinusEqual,
+        Token::Op("*=") => AsNop::MultEqual,
+        Token::Op("/=") => AsNop::DivEqual,
+        Token::Op("%=") => AsNop::ModEqual,
+    }
 }
 
+/// Keywords are lexed as [`Token::Keyword`], so they can't be identifiers.
 fn ident_parser<'src>() -> parser!('src, Identifier<'src>) {
-    #[rustfmt::skip]
-    let prefix = {
-        choice( (
-            one_of('A'..='Z'),
-            one_of('a'..='z'),
-            just('_')
-        ))
-    };
-
-    let suffix = choice((
-        one_of('A'..='Z'),
-        one_of('a'..='z'),
-        one_of('0'..='9'),
-        just('_'),
-    ))
-    .repeated();
-
-    prefix.then(suffix).to_slice().try_map(|ident: &str, span| {
-        if KEYWORDS.contains(&ident) {
-            Err(Rich::custom(span, "nein. doch. oh."))
-        } else {
-            Ok(Identifier(ident))
-        }
-    })
-}
-
-fn decnum_parser<'src>() -> parser!('src, Decnum<'src>) {
-    let decnum = one_of('1'..='9')
-        .then(one_of('0'..='9').repeated())
-        .to_slice()
-        .map(|dec| Decnum(dec));
-
-    let just_zero = just("0").map(|z| Decnum(z));
-
-    choice((decnum, just_zero)).padded_by(padding())
-}
-
-#[rustfmt::skip]
-fn hexnum_parser<'src>() -> parser!('src, Hexnum<'src>) {
-    just('0')
-        .ignore_then(one_of("xX").ignored())
-        .then(
-            choice((
-                one_of('A'..='F'),
-                one_of('a'..='f'),
-                one_of('0'..='9'))
-            )
-            .repeated()
-            .at_least(1)
-            .to_slice()
-        )
-        .padded_by(padding())
-        .map(|(_, hexnum)| Hexnum(hexnum))
+    select! { Token::Ident(ident) => Identifier(ident) }
 }
 
 #[cfg(test)]
 mod tests {
 
     use super::*;
+    use crate::{CompileError, lexer::lex};
+
+    /// Parses code which can be lexed.
+    fn parse(code: &str) -> (Option<Program<'_>>, Vec<Rich<'_, String>>) {
+        parse_tokens(lex(code).into_result().unwrap(), code.len())
+    }
+
+    fn tokens(code: &str) -> Tokens<'_> {
+        tokens_input(lex(code).into_result().unwrap(), code.len())
+    }
 
     // == statements
     #[test]
     fn statements_return() {
         assert_eq!(
-            statements_parser().parse("return 0;").into_result(),
-            Ok(Statements::Statement {
-                current: Statement::Return(Expression::Intconst(Intconst::Decnum(Decnum("0")))),
-                next: Box::new(Statements::None)
-            })
+            statements_parser().parse(tokens("return 0;")).into_result(),
+            Ok(vec![Statement::Return(Some(Expression::Intconst(
+                Intconst::Decnum(Decnum("0"))
+            )))])
         )
     }
 
     #[test]
     fn statements_empty() {
         assert_eq!(
-            statements_parser().parse("").into_result(),
-            Ok(Statements::None)
+            statements_parser().parse(tokens("")).into_result(),
+            Ok(vec![])
         );
     }
 
     #[test]
     fn statements_empty_padded() {
         assert_eq!(
-            statements_parser().parse(" ").into_result(),
-            Ok(Statements::None)
+            statements_parser().parse(tokens(" ")).into_result(),
+            Ok(vec![])
         )
     }
 
+    #[test]
+    fn statements_many() {
+        let code = format!(
+            "int main() {{ int x = 0; {} return x; }}",
+            "x += 1; ".repeat(20_000)
+        );
+        let (program, errors) = parse(&code);
+        let program = program.unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(program.0[0].statements.len(), 20_002);
+        assert!(crate::analyzer::analyze(&program).is_ok());
+    }
+
     // == statement
     #[test]
     fn statement_simple_return() {
         assert_eq!(
-            statement_parser().parse(" return 0; ").into_result(),
-            Ok(Statement::Return(Expression::Intconst(Intconst::Decnum(
-                Decnum("0")
+            statement_parser()
+                .parse(tokens(" return 0; "))
+                .into_result(),
+            Ok(Statement::Return(Some(Expression::Intconst(
+                Intconst::Decnum(Decnum("0"))
             ))))
         );
     }
 
-    // == ident
     #[test]
-    fn ident_simple() {
+    fn statement_void_return() {
         assert_eq!(
-            ident_parser().parse("hello").into_result(),
-            Ok(Identifier("hello"))
+            statement_parser().parse(tokens("return ;")).into_result(),
+            Ok(Statement::Return(None))
         );
     }
 
     #[test]
-    fn ident_full() {
+    fn statement_call() {
         assert_eq!(
-            ident_parser().parse("AZaz_0").into_result(),
-            Ok(Identifier("AZaz_0"))
+            statement_parser().parse(tokens("f(x);")).into_result(),
+            Ok(Statement::Exp(Expression::Call {
+                ident: Identifier("f"),
+                args: vec![Expression::Ident(Identifier("x"))]
+            }))
         );
     }
 
     #[test]
-    fn ident_invalid() {
-        assert!(ident_parser().parse("0no").into_result().is_err());
+    fn statement_assert() {
+        assert_eq!(
+            statement_parser()
+                .parse(tokens(" assert ( true ) ; "))
+                .into_result(),
+            Ok(Statement::Assert(Expression::Boolconst(true)))
+        );
     }
 
     #[test]
-    fn ident_keyword() {
-        assert!(ident_parser().parse("struct").into_result().is_err());
+    fn statement_assert_missing_parens() {
+        assert!(
+            statement_parser()
+                .parse(tokens("assert true;"))
+                .into_result()
+                .is_err()
+        );
     }
 
-    // == decnum
     #[test]
-    fn decnum_parser_zero() {
-        assert_eq!(decnum_parser().parse("0").into_result(), Ok(Decnum("0")));
+    fn statement_print() {
+        assert_eq!(
+            statement_parser()
+                .parse(tokens("print(read());"))
+                .into_result(),
+            Ok(Statement::Print(Expression::Read))
+        );
+    }
+
+    #[test]
+    fn statement_compound_assignment_with_comment() {
+        assert_eq!(
+            statement_parser()
+                .parse(tokens("x /* c */ += // c\n 1;"))
+                .into_result(),
+            Ok(Statement::Simp(SimpleInstruction {
+                lvalue: LValue::Ident(Identifier("x")),
+                asnop: AsNop::PlusEqual,
+                exp: Expression::Intconst(Intconst::Decnum(Decnum("1")))
+            }))
+        );
     }
 
+    // == ident
     #[test]
-    fn decnum_parser_valid_number() {
+    fn ident_simple() {
         assert_eq!(
-            decnum_parser().parse("123").into_result(),
-            Ok(Decnum("123"))
+            ident_parser().parse(tokens("hello")).into_result(),
+            Ok(Identifier("hello"))
         );
     }
 
     #[test]
-    fn decnum_parser_invalid_number() {
-        assert!(decnum_parser().parse("0123").into_result().is_err());
+    fn ident_keyword() {
+        assert!(
+            ident_parser()
+                .parse(tokens("struct"))
+                .into_result()
+                .is_err()
+        );
     }
 
-    // == hexnum
+    // == exp
     #[test]
-    fn hexnum_parser_simple() {
+    fn exp_precedence() {
         assert_eq!(
-            hexnum_parser().parse("0xabc").into_result(),
-            Ok(Hexnum("abc"))
+            exp_parser().parse(tokens("-1 + 2 * 3")).into_result(),
+            Ok(Expression::Binop {
+                left: Box::new(Expression::Unop {
+                    op: UnOperation::Minus,
+                    right: Box::new(Expression::Intconst(Intconst::Decnum(Decnum("1"))))
+                }),
+                op: BinOperation::Plus,
+                right: Box::new(Expression::Binop {
+                    left: Box::new(Expression::Intconst(Intconst::Decnum(Decnum("2")))),
+                    op: BinOperation::Multiplication,
+                    right: Box::new(Expression::Intconst(Intconst::Decnum(Decnum("3"))))
+                })
+            })
         );
     }
 
     #[test]
-    fn hexnum_parser_big_x() {
+    fn exp_hexnum() {
         assert_eq!(
-            hexnum_parser().parse("0Xabc").into_result(),
-            Ok(Hexnum("abc"))
+            exp_parser().parse(tokens("0xabc")).into_result(),
+            Ok(Expression::Intconst(Intconst::Hexnum(Hexnum("abc"))))
         );
     }
 
+    // == decl
     #[test]
-    fn hexnum_parser_missing_numbers() {
-        assert!(hexnum_parser().parse("0x").into_result().is_err());
+    fn decl_string() {
+        assert_eq!(
+            decl_parser()
+                .parse(tokens(r#"string s = "hi""#))
+                .into_result(),
+            Ok(Declaration::IdentExp {
+                ty: Type::String,
+                ident: Identifier("s"),
+                exp: Expression::Stringconst(Stringconst("hi"))
+            })
+        );
     }
 
     // == programs
@@ -432,111 +599,240 @@ mod tests {
     #[test]
     fn empty_main() {
         assert_eq!(
-            parser().parse("int main() { }").into_result(),
-            Ok(Program(Statements::None))
+            parser().parse(tokens("int main() { }")).into_result(),
+            Ok(Program(vec![Function {
+                return_type: Type::Int,
+                ident: Identifier("main"),
+                params: vec![],
+                annotations: vec![],
+                statements: vec![]
+            }]))
         );
     }
 
     #[test]
     fn simple_return_main() {
         assert_eq!(
-            parser().parse("int main() { return 0; }").into_result(),
-            Ok(Program(Statements::Statement {
-                current: Statement::Return(Expression::Intconst(Intconst::Decnum(Decnum("0")))),
-                next: Box::new(Statements::None)
-            }))
+            parser()
+                .parse(tokens("int main() { return 0; }"))
+                .into_result(),
+            Ok(Program(vec![Function {
+                return_type: Type::Int,
+                ident: Identifier("main"),
+                params: vec![],
+                annotations: vec![],
+                statements: vec![Statement::Return(Some(Expression::Intconst(
+                    Intconst::Decnum(Decnum("0"))
+                )))]
+            }]))
         );
     }
 
-    // == comments
     #[test]
-    fn block_comments_1_level() {
+    fn multiple_functions() {
+        let program = parser()
+            .parse(tokens(
+                r#"void f(int x, bool y) { return; }
+                int main() { f(1, true); return 0; }"#,
+            ))
+            .into_result()
+            .unwrap();
+
+        assert_eq!(program.0.len(), 2);
         assert_eq!(
-            block_comment().parse("/* hello there */").into_result(),
-            Ok(())
+            program.0[0].params,
+            vec![
+                Param {
+                    ty: Type::Int,
+                    ident: Identifier("x")
+                },
+                Param {
+                    ty: Type::Bool,
+                    ident: Identifier("y")
+                }
+            ]
         );
     }
 
     #[test]
-    fn block_comments_2_level() {
-        assert_eq!(
-            block_comment()
-                .parse("/* /* hello */ /* there */ */")
-                .into_result(),
-            Ok(())
+    fn no_functions() {
+        assert!(parser().parse(tokens(" ")).into_result().is_err());
+    }
+
+    // == comment test
+    #[test]
+    fn general_comment_test() {
+        let (program, errors) = parse(
+            r#"int main() {
+                    // hello there
+                    /*
+                    general kenobi
+                    */
+                    return 0;
+                }"#,
         );
+
+        assert!(program.is_some());
+        assert!(errors.is_empty());
+    }
+
+    // == errors
+    #[test]
+    fn lexing_error() {
+        assert!(matches!(
+            crate::parse("int main() { return 0 # 1; }"),
+            Err(CompileError::Lexing(errors)) if errors.len() == 1
+        ));
     }
 
     #[test]
-    fn block_comments_doom_level() {
+    fn parsing_error() {
+        let (program, errors) = parse("int main() { return 0;");
+
+        assert!(program.is_none());
+        assert_eq!(errors.len(), 1);
+    }
+
+    // == annotations
+    #[test]
+    fn annotations_line() {
         assert_eq!(
-            block_comment()
-                .parse("/* :peepoScream: /* hello /* */ */ :peepoNo /* there */ */")
+            annotations_parser()
+                .parse(tokens("//@requires true; ensures \\result == 0;\n"))
                 .into_result(),
-            Ok(())
+            Ok(vec![
+                Annotation::Requires(Expression::Boolconst(true)),
+                Annotation::Ensures(Expression::Binop {
+                    left: Box::new(Expression::Result),
+                    op: BinOperation::Equal,
+                    right: Box::new(Expression::Intconst(Intconst::Decnum(Decnum("0"))))
+                })
+            ])
         );
     }
 
     #[test]
-    fn block_comments_open_too_much() {
-        assert!(block_comment().parse("/* /* no */").into_result().is_err());
+    fn annotations_block() {
+        assert_eq!(
+            annotations_parser()
+                .parse(tokens("/*@ assert true; @*/ //@loop_invariant false;"))
+                .into_result(),
+            Ok(vec![
+                Annotation::Assert(Expression::Boolconst(true)),
+                Annotation::LoopInvariant(Expression::Boolconst(false))
+            ])
+        );
     }
 
     #[test]
-    fn block_comments_close_too_much() {
-        assert!(block_comment().parse("/* no */ */").into_result().is_err());
+    fn annotated_main() {
+        let program = parser()
+            .parse(tokens(
+                r#"int main()
+                //@ensures \result == 1;
+                {
+                    int x = 1;
+                    //@assert x == 1;
+                    return x;
+                }"#,
+            ))
+            .into_result()
+            .unwrap();
+
+        assert_eq!(program.0[0].annotations.len(), 1);
     }
 
-    // == line comments
+    // == recovery
     #[test]
-    fn line_comments_simple() {
+    fn recover_multiple_errors() {
+        let (program, errors) = parse("int main() { int x = ; x = 1 +; return x; }");
+
+        assert_eq!(errors.len(), 2);
         assert_eq!(
-            line_comments().parse("// rofl lmao kekw").into_result(),
-            Ok(())
+            program.unwrap().0[0].statements,
+            vec![Statement::Return(Some(Expression::Ident(Identifier("x"))))]
         );
     }
 
     #[test]
-    fn line_comments_nested() {
+    fn recover_missing_semicolon_before_brace() {
+        let (program, errors) = parse("int main() { int x = 1; return x }");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(program.unwrap().0[0].statements.len(), 1);
+    }
+
+    #[test]
+    fn recover_nested_parentheses() {
+        let (program, errors) = parse("int main() { print(f((1 + ), 2)); return 0; }");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(program.unwrap().0[0].statements.len(), 1);
+    }
+
+    #[test]
+    fn recover_unbalanced_parentheses() {
+        let (program, errors) = parse("int main() { print((1; return 0; }");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(program.unwrap().0[0].statements.len(), 1);
+    }
+
+    #[test]
+    fn recover_broken_annotation() {
+        let (program, errors) = parse("int main() { //@assert 1 == ;\n return 0; }");
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(program.unwrap().0[0].statements.len(), 1);
+    }
+
+    #[test]
+    fn recover_broken_function() {
+        let (program, errors) =
+            parse("int f( { return 0; } int g() { return 1 + ; } int main() { return 0; }");
+        let program = program.unwrap();
+
+        assert_eq!(errors.len(), 2);
+        assert_eq!(program.0.len(), 2);
+        assert_eq!(program.0[1].ident, Identifier("main"));
+    }
+
+    // == maximal munch
+    #[test]
+    fn keyword_prefix_of_assigned_ident() {
         assert_eq!(
-            line_comments().parse("// rofl // lmao kekw").into_result(),
-            Ok(())
+            statement_parser().parse(tokens("intx = 3;")).into_result(),
+            Ok(Statement::Simp(SimpleInstruction {
+                lvalue: LValue::Ident(Identifier("intx")),
+                asnop: AsNop::Equal,
+                exp: Expression::Intconst(Intconst::Decnum(Decnum("3")))
+            }))
         );
     }
 
-    // == comment test
     #[test]
-    fn general_comment_test() {
-        assert!(
-            parser()
-                .parse(
-                    r#"int main() {
-                    // hello there
-                    /*
-                    general kenobi
-                    */
-                    return 0;
-                }"#
-                )
-                .into_result()
-                .is_ok()
+    fn keyword_prefix_of_ident_exp() {
+        assert_eq!(
+            statement_parser().parse(tokens("returnx;")).into_result(),
+            Ok(Statement::Exp(Expression::Ident(Identifier("returnx"))))
         );
     }
 
-    // == sandbox
-    #[test]
-    fn sandbox() {
-        // panic!(
-        //     "{:#?}",
-        //     parser()
-        //         .parse(
-        //             "int main() {
-        //     intx = 3;
-        //     return x;
-        // }
-        // ",
-        //         )
-        //         .unwrap()
-        // );
+    #[test]
+    fn keyword_prefix_in_program() {
+        let (program, errors) = parse("int main() { intx = 3; return x; }");
+
+        assert!(errors.is_empty());
+        assert_eq!(
+            program.unwrap().0[0].statements,
+            vec![
+                Statement::Simp(SimpleInstruction {
+                    lvalue: LValue::Ident(Identifier("intx")),
+                    asnop: AsNop::Equal,
+                    exp: Expression::Intconst(Intconst::Decnum(Decnum("3")))
+                }),
+                Statement::Return(Some(Expression::Ident(Identifier("x"))))
+            ]
+        );
     }
 }
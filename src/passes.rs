@@ -0,0 +1,190 @@
+//! Runs the front-end checks, the lowering into the IR and the optimisations by name.
+
+use std::time::{Duration, Instant};
+
+use crate::{analyzer, grammar, ir, optimize};
+
+pub type Lowering = for<'src> fn(&grammar::Program<'src>, bool) -> ir::Program<'src>;
+
+#[derive(Debug, Clone, Copy)]
+pub enum Pass {
+    /// Fails if the AST is invalid.
+    Check(analyzer::Check),
+    /// Turns the AST into the IR, the flag tells if runtime checks for annotations are emitted.
+    Lower(Lowering),
+    /// Transforms the IR, so it has to come after the lowering.
+    Optimize(optimize::Optimization),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Check the annotations of the program at runtime.
+    pub dynamic_checks: bool,
+    /// Print how long every pass took to stderr.
+    pub time_passes: bool,
+    /// Print the program to stderr after the pass with this name.
+    pub dump_after: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct PassManager {
+    passes: Vec<(&'static str, Pass)>,
+    options: Options,
+}
+
+/// The program between two passes.
+enum Stage<'a, 'src> {
+    Ast(&'a grammar::Program<'src>),
+    Ir(ir::Program<'src>),
+}
+
+impl PassManager {
+    pub fn new(options: Options) -> Self {
+        Self {
+            passes: Vec::new(),
+            options,
+        }
+    }
+
+    /// Only checks the program, e.g. to interpret it afterwards.
+    pub fn front_end(options: Options) -> Self {
+        let mut manager = Self::new(options);
+        for (name, check) in analyzer::CHECKS {
+            manager.add(name, Pass::Check(*check));
+        }
+
+        manager
+    }
+
+    /// Checks, lowers and optimises the program.
+    pub fn compiler(options: Options) -> Self {
+        let mut manager = Self::front_end(options);
+        manager.add("lower", Pass::Lower(ir::lower::lower));
+        for (name, optimization) in optimize::OPTIMIZATIONS {
+            manager.add(name, Pass::Optimize(*optimization));
+        }
+
+        manager
+    }
+
+    pub fn add(&mut self, name: &'static str, pass: Pass) {
+        self.passes.push((name, pass));
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.passes.iter().any(|(pass, _)| *pass == name)
+    }
+
+    /// Runs all passes in order and returns the IR if the program has been lowered.
+    /// Returns the name of the check which failed otherwise.
+    pub fn run<'src>(
+        &self,
+        program: &grammar::Program<'src>,
+    ) -> Result<Option<ir::Program<'src>>, &'static str> {
+        let mut stage = Stage::Ast(program);
+        let mut total = Duration::ZERO;
+
+        for (name, pass) in &self.passes {
+            let start = Instant::now();
+            stage = match (pass, stage) {
+                (Pass::Check(check), Stage::Ast(program)) => {
+                    check(program).map_err(|_| *name)?;
+                    Stage::Ast(program)
+                }
+                (Pass::Lower(lower), Stage::Ast(program)) => {
+                    Stage::Ir(lower(program, self.options.dynamic_checks))
+                }
+                (Pass::Optimize(optimize), Stage::Ir(mut program)) => {
+                    optimize(&mut program);
+                    Stage::Ir(program)
+                }
+                (_, _) => panic!("the pass {} doesn't fit the program at this point", name),
+            };
+
+            let elapsed = start.elapsed();
+            total += elapsed;
+            if self.options.time_passes {
+                eprintln!("{:>12.3?}  {}", elapsed, name);
+            }
+
+            if self.options.dump_after.as_deref() == Some(*name) {
+                match &stage {
+                    Stage::Ast(program) => eprintln!("{:#?}", program),
+                    Stage::Ir(program) => eprint!("{}", program),
+                }
+            }
+        }
+
+        if self.options.time_passes {
+            eprintln!("{:>12.3?}  total", total);
+        }
+
+        match stage {
+            Stage::Ast(_) => Ok(None),
+            Stage::Ir(program) => Ok(Some(program)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(code: &str) -> grammar::Program<'_> {
+        crate::parse(code).unwrap()
+    }
+
+    #[test]
+    fn front_end_only() {
+        let program = parse("int main() { return 0; }");
+
+        assert_eq!(
+            PassManager::front_end(Options::default()).run(&program),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn failed_check() {
+        let program = parse("int main() { return x; }");
+
+        assert_eq!(
+            PassManager::compiler(Options::default()).run(&program),
+            Err("variable_dec_init_and_usage")
+        );
+    }
+
+    #[test]
+    fn compiler() {
+        let program = parse("int main() { int x = 1; return x; }");
+        let ir = PassManager::compiler(Options::default())
+            .run(&program)
+            .unwrap()
+            .unwrap();
+
+        // the copy has been propagated
+        assert_eq!(
+            ir.functions[0].blocks[0].terminator,
+            ir::Terminator::Return(Some(ir::Operand::Const(1)))
+        );
+    }
+
+    #[test]
+    fn registered_passes() {
+        let manager = PassManager::compiler(Options::default());
+
+        assert!(manager.contains("type_check"));
+        assert!(manager.contains("lower"));
+        assert!(manager.contains("copy_propagation"));
+        assert!(!manager.contains("lowering"));
+    }
+
+    #[test]
+    #[should_panic]
+    fn optimization_before_lowering() {
+        let mut manager = PassManager::new(Options::default());
+        manager.add("copy_propagation", Pass::Optimize(|_| {}));
+
+        let _ = manager.run(&parse("int main() { return 0; }"));
+    }
+}
@@ -0,0 +1,390 @@
+//! Runs test suites in the format of the lecture.
+//!
+//! Every test is a file whose first line is a header which tells what should happen:
+//!
+//! - `//test return <n>`: the program exits with `<n>`, modulo 256 like every exit code
+//! - `//test error`: compiling fails with a lexing, parsing or semantic error (exit code 42 or 7)
+//! - `//test typecheck`: the program compiles, it isn't run
+//! - `//test div-by-zero`: the program gets killed by `SIGFPE`
+//! - `//test abort`: the program gets killed by `SIGABRT`
+//! - `//test memerror`: the program gets killed by `SIGSEGV` or `SIGUSR2`
+//! - `//test infloop`: the program doesn't terminate before the timeout
+//!
+//! Files without a header are ignored. The program reads its input from `<file>.in` if it exists.
+
+use std::{
+    fmt,
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use crate::codegen::toolchain::TempDir;
+
+const SIGABRT: i32 = 6;
+const SIGFPE: i32 = 8;
+const SIGSEGV: i32 = 11;
+const SIGUSR2: i32 = 12;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Options {
+    /// The directory which contains the tests, subdirectories are searched as well.
+    pub dir: PathBuf,
+    /// The compiler which is tested.
+    pub compiler: PathBuf,
+    /// How long compiling and running a test may take each.
+    pub timeout: Duration,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expectation {
+    Return(i32),
+    Error,
+    Typecheck,
+    DivByZero,
+    Abort,
+    MemError,
+    Infloop,
+}
+
+/// What happened to a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Termination {
+    Exited(i32),
+    Signaled(i32),
+    TimedOut,
+}
+
+/// Runs every test in the directory, prints the failed tests and a summary.
+/// Returns the exit code of the runner, which is `1` if any test failed.
+pub fn run(options: &Options) -> i32 {
+    let mut tests = Vec::new();
+    if let Err(error) = collect_tests(&options.dir, &mut tests) {
+        eprintln!("couldn't read {}: {}", options.dir.display(), error);
+        return 2;
+    }
+    tests.sort_by(|(left, _), (right, _)| left.cmp(right));
+
+    let failed = AtomicUsize::new(0);
+    in_parallel(&tests, |(path, expectation)| {
+        if let Err(message) = run_test(options, path, *expectation) {
+            eprintln!("FAIL {}: {}", path.display(), message);
+            failed.fetch_add(1, Ordering::SeqCst);
+        }
+    });
+
+    let failed = failed.into_inner();
+    println!("{} passed, {} failed", tests.len() - failed, failed);
+
+    match failed {
+        0 => 0,
+        _ => 1,
+    }
+}
+
+/// Calls `f` for every item, using as many threads as there are cores.
+pub(crate) fn in_parallel<'a, T: Sync>(items: &'a [T], f: impl Fn(&'a T) + Sync) {
+    let next = AtomicUsize::new(0);
+    let workers = std::thread::available_parallelism().map_or(1, usize::from);
+
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                while let Some(item) = items.get(next.fetch_add(1, Ordering::SeqCst)) {
+                    f(item);
+                }
+            });
+        }
+    });
+}
+
+/// Finds the files with a test header.
+pub(crate) fn collect_tests(
+    dir: &Path,
+    tests: &mut Vec<(PathBuf, Expectation)>,
+) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_tests(&path, tests)?;
+            continue;
+        }
+
+        let Ok(code) = std::fs::read_to_string(&path) else {
+            continue;
+        };
+        match parse_header(&code) {
+            Some(Ok(expectation)) => tests.push((path, expectation)),
+            Some(Err(header)) => {
+                eprintln!("{}: unknown test header `{}`", path.display(), header);
+            }
+            None => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns `None` if the first line isn't a header and the header if it's unknown.
+pub fn parse_header(code: &str) -> Option<Result<Expectation, &str>> {
+    let header = code
+        .lines()
+        .next()?
+        .strip_prefix("//test")
+        .filter(|rest| rest.is_empty() || rest.starts_with(char::is_whitespace))?
+        .trim();
+
+    let expectation = match header.split_whitespace().collect::<Vec<_>>()[..] {
+        ["return", value] => match value.parse() {
+            Ok(value) => Expectation::Return(value),
+            Err(_) => return Some(Err(header)),
+        },
+        ["error"] => Expectation::Error,
+        ["typecheck"] => Expectation::Typecheck,
+        ["div-by-zero"] => Expectation::DivByZero,
+        ["abort"] => Expectation::Abort,
+        ["memerror"] => Expectation::MemError,
+        ["infloop"] => Expectation::Infloop,
+        _ => return Some(Err(header)),
+    };
+
+    Some(Ok(expectation))
+}
+
+fn run_test(options: &Options, path: &Path, expectation: Expectation) -> Result<(), String> {
+    let dir = TempDir::new().map_err(|error| format!("couldn't create a directory: {}", error))?;
+    let executable = dir.path.join("test");
+
+    let compiler = Command::new(&options.compiler)
+        .arg(path)
+        .arg("-o")
+        .arg(&executable)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|error| format!("couldn't run the compiler: {}", error))?;
+    let compiled = wait(compiler, options.timeout)?;
+
+    let run = match compiled {
+        Termination::Exited(0)
+            if !matches!(expectation, Expectation::Typecheck | Expectation::Error) =>
+        {
+            let program = Command::new(&executable)
+                .stdin(input(path))
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .map_err(|error| format!("couldn't run the program: {}", error))?;
+            Some(wait(program, options.timeout)?)
+        }
+        _ => None,
+    };
+
+    check(expectation, compiled, run)
+}
+
+/// The input of the test, which is `<file>.in` if it exists.
+pub(crate) fn input(test: &Path) -> Stdio {
+    let mut name = test.file_name().unwrap_or_default().to_os_string();
+    name.push(".in");
+
+    match std::fs::File::open(test.with_file_name(name)) {
+        Ok(file) => Stdio::from(file),
+        Err(_) => Stdio::null(),
+    }
+}
+
+/// Compares what happened to the compiler and the program, if it ran, with the expectation.
+pub fn check(
+    expectation: Expectation,
+    compiled: Termination,
+    run: Option<Termination>,
+) -> Result<(), String> {
+    let passed = match (expectation, run) {
+        (Expectation::Error, _) => matches!(compiled, Termination::Exited(42 | 7)),
+        (Expectation::Typecheck, _) => compiled == Termination::Exited(0),
+        (_, None) => false,
+        (Expectation::Return(value), Some(run)) => run == Termination::Exited(value & 0xff),
+        (Expectation::DivByZero, Some(run)) => run == Termination::Signaled(SIGFPE),
+        (Expectation::Abort, Some(run)) => run == Termination::Signaled(SIGABRT),
+        (Expectation::MemError, Some(run)) => {
+            matches!(run, Termination::Signaled(SIGSEGV | SIGUSR2))
+        }
+        (Expectation::Infloop, Some(run)) => run == Termination::TimedOut,
+    };
+    if passed {
+        return Ok(());
+    }
+
+    match run {
+        Some(run) => Err(format!("expected {}, but the program {}", expectation, run)),
+        None => Err(format!(
+            "expected {}, but the compiler {}",
+            expectation, compiled
+        )),
+    }
+}
+
+/// Waits for the process and kills it once the timeout is over.
+pub(crate) fn wait(mut child: Child, timeout: Duration) -> Result<Termination, String> {
+    let start = Instant::now();
+
+    loop {
+        let status = child
+            .try_wait()
+            .map_err(|error| format!("couldn't wait for a process: {}", error))?;
+        if let Some(status) = status {
+            return Ok(termination(status));
+        }
+
+        if start.elapsed() >= timeout {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(Termination::TimedOut);
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn termination(status: ExitStatus) -> Termination {
+    match status.code() {
+        Some(code) => Termination::Exited(code),
+        None => Termination::Signaled(status.signal().expect("processes exit or get killed")),
+    }
+}
+
+impl fmt::Display for Expectation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expectation::Return(value) => write!(f, "return {}", value),
+            Expectation::Error => write!(f, "error"),
+            Expectation::Typecheck => write!(f, "typecheck"),
+            Expectation::DivByZero => write!(f, "div-by-zero"),
+            Expectation::Abort => write!(f, "abort"),
+            Expectation::MemError => write!(f, "memerror"),
+            Expectation::Infloop => write!(f, "infloop"),
+        }
+    }
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Termination::Exited(code) => write!(f, "exited with {}", code),
+            Termination::Signaled(signal) => write!(f, "got killed by signal {}", signal),
+            Termination::TimedOut => write!(f, "timed out"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers() {
+        assert_eq!(
+            parse_header("//test return 42\nint main() {}"),
+            Some(Ok(Expectation::Return(42)))
+        );
+        assert_eq!(
+            parse_header("//test return -1"),
+            Some(Ok(Expectation::Return(-1)))
+        );
+        assert_eq!(
+            parse_header("//test div-by-zero\n"),
+            Some(Ok(Expectation::DivByZero))
+        );
+        assert_eq!(
+            parse_header("//test infloop"),
+            Some(Ok(Expectation::Infloop))
+        );
+        assert_eq!(parse_header("//test\tabort"), Some(Ok(Expectation::Abort)));
+        assert_eq!(parse_header("//test retrun 1"), Some(Err("retrun 1")));
+        assert_eq!(parse_header("//testfoo error"), None);
+        assert_eq!(parse_header("//tests return 1"), None);
+        assert_eq!(parse_header("int main() {}"), None);
+        assert_eq!(parse_header(""), None);
+    }
+
+    #[test]
+    fn expectations() {
+        let compiled = Termination::Exited(0);
+
+        assert!(
+            check(
+                Expectation::Return(-1),
+                compiled,
+                Some(Termination::Exited(255))
+            )
+            .is_ok()
+        );
+        assert!(
+            check(
+                Expectation::Return(1),
+                compiled,
+                Some(Termination::Exited(2))
+            )
+            .is_err()
+        );
+        assert!(check(Expectation::Error, Termination::Exited(42), None).is_ok());
+        assert!(check(Expectation::Error, Termination::Exited(7), None).is_ok());
+        assert!(check(Expectation::Error, Termination::Exited(101), None).is_err());
+        assert!(check(Expectation::Typecheck, compiled, None).is_ok());
+        assert!(check(Expectation::Typecheck, Termination::Exited(7), None).is_err());
+        assert!(check(Expectation::Abort, compiled, Some(Termination::Signaled(6))).is_ok());
+        assert!(
+            check(
+                Expectation::DivByZero,
+                compiled,
+                Some(Termination::Signaled(6))
+            )
+            .is_err()
+        );
+        assert!(check(Expectation::Infloop, compiled, Some(Termination::TimedOut)).is_ok());
+        assert!(check(Expectation::Return(0), Termination::Exited(42), None).is_err());
+    }
+
+    #[test]
+    fn suite() {
+        let dir = TempDir::new().unwrap();
+        let test = |name: &str, code: &str| std::fs::write(dir.path.join(name), code).unwrap();
+        // `sh` is the compiler, so every test writes its own executable to the path after `-o`
+        let program = |header: &str, body: &str| {
+            format!(
+                "{}\nprintf '#!/bin/sh\\n{}\\n' > \"$2\" && chmod +x \"$2\"\n",
+                header, body
+            )
+        };
+        test("return.c0", &program("//test return 3", "exit 3"));
+        test("input.c0", &program("//test return 7", "read x; exit $x"));
+        test("input.c0.in", "7\n");
+        test("error.c0", "//test error\nexit 42\n");
+        test("infloop.c0", &program("//test infloop", "sleep 10"));
+        test("no_header.c0", "exit 1\n");
+        let options = Options {
+            dir: dir.path.clone(),
+            compiler: "sh".into(),
+            timeout: Duration::from_millis(500),
+        };
+
+        assert_eq!(run(&options), 0);
+
+        test("wrong.c0", &program("//test return 1", "exit 2"));
+        test("timeout.c0", &program("//test return 0", "sleep 10"));
+        assert_eq!(run(&options), 1);
+    }
+
+    #[test]
+    fn timeout() {
+        let child = Command::new("sleep").arg("10").spawn().unwrap();
+
+        assert_eq!(
+            wait(child, Duration::from_millis(50)),
+            Ok(Termination::TimedOut)
+        );
+    }
+}